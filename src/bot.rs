@@ -0,0 +1,98 @@
+use crate::checkers;
+use crate::game;
+
+// What a bot may spend on one move.
+#[derive(Clone, Copy, Debug)]
+pub struct SearchLimits {
+    // milliseconds to think for
+    pub time_ms: u64,
+}
+
+// When a search has to stop. The clock is only looked at every so many nodes
+// so that polling it costs next to nothing.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    end: std::time::Instant,
+    nodes: u64,
+    stopped: bool,
+}
+
+const NODES_PER_POLL: u64 = 1024;
+
+impl Deadline {
+    pub fn new(limits: SearchLimits) -> Deadline {
+        Deadline {
+            end: std::time::Instant::now() + std::time::Duration::from_millis(limits.time_ms),
+            nodes: 0,
+            stopped: false,
+        }
+    }
+
+    // Counts a node searched, true once the time is up. A search should
+    // return straight away when it is, whatever it has found so far.
+    pub fn poll(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.stopped && self.nodes.is_multiple_of(NODES_PER_POLL) {
+            self.stopped = std::time::Instant::now() >= self.end;
+        }
+        self.stopped
+    }
+
+    // Looks at the clock now, for use between searches.
+    pub fn out_of_time(&mut self) -> bool {
+        if !self.stopped {
+            self.stopped = std::time::Instant::now() >= self.end;
+        }
+        self.stopped
+    }
+
+    // whether time ran out, in which case the search going on at the time was
+    // cut short and its result can't be trusted
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+}
+
+// The move a bot picked and what it makes of the position.
+#[derive(Clone, Copy, Debug)]
+pub struct SearchResult {
+    pub best_move: checkers::Move,
+    // positive when black is ahead, a man being worth 1
+    pub score: f64,
+    // the deepest search finished, 0 for a bot that doesn't search
+    pub depth: u32,
+    // positions searched, over every depth tried
+    pub nodes: u64,
+}
+
+// A bot that can play either side of a game. Choosing a move takes the bot
+// mutably so a bot can keep what it learned from one move to the next.
+pub trait Bot {
+    // the name the bot goes by, e.g. "alphabeta"
+    fn name(&self) -> &str;
+
+    // the settings it has away from their defaults, as name=value pairs
+    // separated by commas
+    fn configuration(&self) -> String;
+
+    // Sets one of its settings from text, failing with InvalidBotOption when
+    // it has no such setting or the value doesn't fit it.
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), checkers::CheckersError>;
+
+    // the name followed by the settings, e.g. "alphabeta:depth=8", or just
+    // the name when there are none
+    fn label(&self) -> String {
+        match self.configuration().as_str() {
+            "" => self.name().to_string(),
+            configuration => format!("{}:{}", self.name(), configuration),
+        }
+    }
+
+    // Picks a move for the side to move in `game`, failing with NoLegalMoves
+    // when there is none.
+    fn choose_move(&mut self, game: &game::Game, limits: SearchLimits) -> Result<SearchResult, checkers::CheckersError>;
+}
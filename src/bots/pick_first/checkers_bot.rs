@@ -1,4 +1,6 @@
+use crate::bot;
 use crate::checkers;
+use crate::game;
 
 pub const PIECE_VALUE: f64 = 1.0;
 pub const KING_VALUE: f64 = 3.0;
@@ -19,12 +21,32 @@ pub fn evaluate_board(board: &checkers::Board) -> f64 {
     score
 }
 
-pub fn pick_move(board: &mut checkers::Board, color: u8, time_restraint_ms: u64) -> (checkers::Move, f64) {
-    let moves = match color {
-        checkers::BLACK => board.search_black_all(),
-        checkers::WHITE => board.search_white_all(),
-        _ => panic!("Invalid color"),
-    };
+// Plays the first legal move it finds, as a baseline for the others.
+#[derive(Clone, Debug, Default)]
+pub struct PickFirst;
 
-    (moves[0].clone(), evaluate_board(board))
+impl bot::Bot for PickFirst {
+    fn name(&self) -> &str {
+        "pick_first"
+    }
+
+    fn configuration(&self) -> String {
+        String::new()
+    }
+
+    fn set_option(&mut self, name: &str, _value: &str) -> Result<(), checkers::CheckersError> {
+        Err(checkers::CheckersError::InvalidBotOption(format!("{} has no option {}", self.name(), name)))
+    }
+
+    fn choose_move(&mut self, game: &game::Game, _limits: bot::SearchLimits) -> Result<bot::SearchResult, checkers::CheckersError> {
+        match game.legal_moves().first() {
+            Some(m) => Ok(bot::SearchResult {
+                best_move: *m,
+                score: evaluate_board(game.board()),
+                depth: 0,
+                nodes: 0,
+            }),
+            None => Err(checkers::CheckersError::NoLegalMoves),
+        }
+    }
 }
@@ -1,5 +1,3 @@
-use std::collections::HashSet;
-
 pub const WHITE: u8 = 0b0001;
 pub const BLACK: u8 = 0b0010;
 pub const KING: u8 = 0b0100;
@@ -7,96 +5,837 @@ pub const WHITE_KING: u8 = WHITE | KING;
 pub const BLACK_KING: u8 = BLACK | KING;
 pub const EMPTY: u8 = 0;
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+// The playable squares are packed into a bitboard, bit n being the nth dark
+// square of the board array counting from index 0. Every row holds half a
+// row's worth of playable squares; even rows start on an odd array index,
+// odd rows on an even one.
+type Bitboard = u64;
+
+// the largest board, 10x10, and its playable squares
+const MAX_BOARD_SIZE: usize = 10;
+const MAX_SQUARES: usize = 50;
+
+// the most squares a move can list: capturing all 20 pieces on the 10x10
+// board lands on 21 squares
+const MAX_MOVE_SQUARES: usize = 21;
+
+// Directions are named by the offset they add to an index of the 8x8 board
+// array. Black moves up the board, white moves down.
+const UP_LEFT: i8 = -9;
+const UP_RIGHT: i8 = -7;
+const DOWN_LEFT: i8 = 7;
+const DOWN_RIGHT: i8 = 9;
+const ALL_DIRECTIONS: [i8; 4] = [UP_LEFT, UP_RIGHT, DOWN_LEFT, DOWN_RIGHT];
+
+// What happens when a man reaches the back row in the middle of a capture.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Crowning {
+    // it is crowned and the move ends there
+    EndsMove,
+    // it is crowned and carries on capturing as a king
+    ContinuesAsKing,
+    // it carries on capturing as a man, and is only crowned if the move
+    // finishes on the back row
+    AtEndOfMove,
+}
+
+// The rules that differ between the checkers variants the move generator
+// can play.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Rules {
+    // squares along each side of the board, 8 or 10
+    pub board_size: u8,
+    // a capture has to take as many pieces as any capture available
+    pub majority_capture: bool,
+    pub crowning: Crowning,
+    // men may capture backwards as well as forwards
+    pub men_capture_backwards: bool,
+    // kings move and capture any distance along a diagonal
+    pub flying_kings: bool,
+    pub first_to_move: u8,
+}
+
+// American/English checkers (ACF rules): any capture may be chosen, but it
+// has to be jumped out to the end, and crowning ends the move.
+pub const AMERICAN_RULES: Rules = Rules {
+    board_size: 8,
+    majority_capture: false,
+    crowning: Crowning::EndsMove,
+    men_capture_backwards: false,
+    flying_kings: false,
+    first_to_move: BLACK,
+};
+
+// The rules this engine originally played: the capture taking the most
+// pieces has to be chosen, and a man crowned mid-capture keeps jumping as a
+// king.
+pub const MAJORITY_CAPTURE_RULES: Rules = Rules {
+    board_size: 8,
+    majority_capture: true,
+    crowning: Crowning::ContinuesAsKing,
+    men_capture_backwards: false,
+    flying_kings: false,
+    first_to_move: BLACK,
+};
+
+// Russian draughts: men capture backwards, kings fly, any capture may be
+// chosen and a man crowned mid-capture carries on as a king. White starts.
+pub const RUSSIAN_RULES: Rules = Rules {
+    board_size: 8,
+    majority_capture: false,
+    crowning: Crowning::ContinuesAsKing,
+    men_capture_backwards: true,
+    flying_kings: true,
+    first_to_move: WHITE,
+};
+
+// International draughts, played on a 10x10 board with 20 men a side: men
+// capture backwards, kings fly and the capture taking the most pieces has
+// to be chosen. A man only passing over the back row stays a man. White
+// starts.
+pub const INTERNATIONAL_RULES: Rules = Rules {
+    board_size: 10,
+    majority_capture: true,
+    crowning: Crowning::AtEndOfMove,
+    men_capture_backwards: true,
+    flying_kings: true,
+    first_to_move: WHITE,
+};
+
+// Everything that can go wrong when the engine is handed bad input.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum CheckersError {
+    // a square number that isn't on the board
+    InvalidSquare(String),
+    // text that isn't a move in standard notation
+    InvalidNotation(String),
+    // a capture written without every jump, which can't be read without a
+    // board
+    IncompleteCapture(String),
+    IllegalMove(String),
+    // notation matching more than one legal move
+    AmbiguousMove(String),
+    InvalidFen(String),
+    InvalidPdn(String),
+    // a move that can't be played on the board at all, e.g. one starting
+    // on an empty square
+    InvalidMove(String),
+    // a value that isn't one of the piece constants
+    InvalidPiece(u8),
+    NoLegalMoves,
+    GameOver,
+    UnknownBot(String),
+    // a bot option that the bot doesn't take or a value it can't use
+    InvalidBotOption(String),
+}
+
+impl std::fmt::Display for CheckersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CheckersError::InvalidSquare(text) => write!(f, "Invalid square number: {}", text),
+            CheckersError::InvalidNotation(text) => write!(f, "Invalid move: {}", text),
+            CheckersError::IncompleteCapture(text) => write!(f, "Incomplete capture, every jump must be given: {}", text),
+            CheckersError::IllegalMove(text) => write!(f, "Illegal move: {}", text),
+            CheckersError::AmbiguousMove(text) => write!(f, "Ambiguous move: {}", text),
+            CheckersError::InvalidFen(reason) => write!(f, "Invalid FEN, {}", reason),
+            CheckersError::InvalidPdn(reason) => write!(f, "Invalid PDN, {}", reason),
+            CheckersError::InvalidMove(reason) => write!(f, "Invalid move, {}", reason),
+            CheckersError::InvalidPiece(piece) => write!(f, "Invalid piece: {}", piece),
+            CheckersError::NoLegalMoves => write!(f, "No legal moves"),
+            CheckersError::GameOver => write!(f, "The game is over"),
+            CheckersError::UnknownBot(name) => write!(f, "Unknown bot: {}", name),
+            CheckersError::InvalidBotOption(reason) => write!(f, "Invalid bot option, {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CheckersError {}
+
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const fn zobrist_piece_keys() -> [[u64; MAX_SQUARES]; 4] {
+    let mut keys = [[0; MAX_SQUARES]; 4];
+    let mut piece = 0;
+    while piece < 4 {
+        let mut square = 0;
+        while square < MAX_SQUARES {
+            keys[piece][square] = splitmix64(0x9E37_79B9_7F4A_7C15u64.wrapping_mul((piece * MAX_SQUARES + square + 1) as u64));
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+// Zobrist keys, indexed by black man, black king, white man, white king and
+// then by bitboard square. The position key is the xor of the keys of every
+// piece on the board, plus ZOBRIST_WHITE_TO_MOVE when white is to move.
+const ZOBRIST_PIECES: [[u64; MAX_SQUARES]; 4] = zobrist_piece_keys();
+const ZOBRIST_WHITE_TO_MOVE: u64 = splitmix64(0x9E37_79B9_7F4A_7C15u64.wrapping_mul(4 * MAX_SQUARES as u64 + 1));
+
+fn zobrist_key(square: u8, piece: u8) -> u64 {
+    let kind = match piece {
+        BLACK => 0,
+        BLACK_KING => 1,
+        WHITE => 2,
+        _ => 3,
+    };
+    ZOBRIST_PIECES[kind][square as usize]
+}
+
+// The square numbering and bitboard masks of one board size.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+struct Geometry {
+    size: u8,
+    row_squares: u8,
+    squares: u8,
+    all: Bitboard,
+    even_rows: Bitboard,
+    odd_rows: Bitboard,
+    left_edge: Bitboard,
+    right_edge: Bitboard,
+    top_row: Bitboard,
+    bottom_row: Bitboard,
+}
+
+impl Geometry {
+    const fn new(size: u8) -> Geometry {
+        let row_squares = size / 2;
+        let squares = size * row_squares;
+        let top_row: Bitboard = (1 << row_squares) - 1;
+        let all: Bitboard = (1 << squares) - 1;
+
+        let mut even_rows = 0;
+        let mut left_edge = 0;
+        let mut right_edge = 0;
+        let mut row = 0;
+        while row < size {
+            let first = row * row_squares;
+            if row % 2 == 0 {
+                even_rows |= top_row << first;
+            }
+            left_edge |= 1 << first;
+            right_edge |= 1 << (first + row_squares - 1);
+            row += 1;
+        }
+
+        Geometry {
+            size,
+            row_squares,
+            squares,
+            all,
+            even_rows,
+            odd_rows: all & !even_rows,
+            left_edge,
+            right_edge,
+            top_row,
+            bottom_row: top_row << (squares - row_squares),
+        }
+    }
+
+    fn square_to_index(&self, square: u8) -> u8 {
+        let row = square / self.row_squares;
+        row * self.size + (square % self.row_squares) * 2 + (1 - row % 2)
+    }
+
+    // Standard notation numbers the playable squares from 1 starting at
+    // black's back rank, so square 1 is the last bitboard square.
+    fn number_to_square(&self, number: u8) -> u8 {
+        self.squares - number
+    }
+
+    fn number_to_index(&self, number: u8) -> u8 {
+        self.square_to_index(self.number_to_square(number))
+    }
+
+    fn index_to_number(&self, index: u8) -> u8 {
+        self.squares - index_to_square(index)
+    }
+
+    fn parse_square_number(&self, text: &str) -> Result<u8, CheckersError> {
+        match text.trim().parse::<u8>() {
+            Ok(number) if (1..=self.squares).contains(&number) => Ok(number),
+            _ => Err(CheckersError::InvalidSquare(text.to_string())),
+        }
+    }
+
+    // Splits "11-15" or "22x15x8" into its square numbers, and whether it
+    // was written as a capture.
+    fn parse_notation(&self, text: &str) -> Result<(Vec<u8>, bool), CheckersError> {
+        let text = text.trim();
+        let capture = text.contains('x');
+        let separator = match capture {
+            true => 'x',
+            false => '-',
+        };
+
+        let numbers = text.split(separator).map(|number| self.parse_square_number(number)).collect::<Result<Vec<u8>, CheckersError>>()?;
+        if numbers.len() < 2 {
+            return Err(CheckersError::InvalidNotation(text.to_string()));
+        }
+
+        Ok((numbers, capture))
+    }
+
+    fn shift(&self, bits: Bitboard, direction: i8) -> Bitboard {
+        let w = self.row_squares;
+        let shifted = match direction {
+            UP_LEFT => ((bits & self.even_rows) >> w) | ((bits & self.odd_rows & !self.left_edge) >> (w + 1)),
+            UP_RIGHT => ((bits & self.even_rows & !self.right_edge) >> (w - 1)) | ((bits & self.odd_rows) >> w),
+            DOWN_LEFT => ((bits & self.even_rows) << w) | ((bits & self.odd_rows & !self.left_edge) << (w - 1)),
+            DOWN_RIGHT => ((bits & self.even_rows & !self.right_edge) << (w + 1)) | ((bits & self.odd_rows) << w),
+            _ => 0,
+        };
+        shifted & self.all
+    }
+
+    fn promotion_row(&self, color: u8) -> Bitboard {
+        match color {
+            BLACK => self.top_row,
+            _ => self.bottom_row,
+        }
+    }
+
+    // The squares the piece on `from` can move to without capturing.
+    fn quiet_targets(&self, from: u8, color: u8, king: bool, flying_kings: bool, empty: Bitboard) -> Bitboard {
+        let mut targets = 0;
+
+        for &direction in directions(color, king) {
+            let mut to = self.shift(1 << from, direction) & empty;
+            targets |= to;
+
+            if king && flying_kings {
+                while to != 0 {
+                    to = self.shift(to, direction) & empty;
+                    targets |= to;
+                }
+            }
+        }
+
+        targets
+    }
+
+    fn quiet_move(&self, from: u8, to: u8, color: u8, king: bool) -> Move {
+        let mut new_move = Move::new(color);
+        new_move.board_size = self.size;
+        new_move.jumps.push(self.square_to_index(from));
+        new_move.jumps.push(self.square_to_index(to));
+        if !king && self.promotion_row(color) & (1 << to) != 0 {
+            new_move.is_king = true;
+        }
+        new_move
+    }
+}
+
+// The board array is sized for the largest board; smaller boards only use
+// the start of it.
+#[derive(Clone, Copy, Debug)]
 pub struct Board {
-    pub board: Vec<u8>,
-    black_pieces_bitboard: u128,
-    white_pieces_bitboard: u128,
-    black_kings_bitboard: u128,
-    white_kings_bitboard: u128,
+    pub board: [u8; MAX_BOARD_SIZE * MAX_BOARD_SIZE],
+    black_pieces_bitboard: Bitboard,
+    white_pieces_bitboard: Bitboard,
+    black_kings_bitboard: Bitboard,
+    white_kings_bitboard: Bitboard,
     turn: u8,
+    hash: u64,
+    rules: Rules,
+    geometry: Geometry,
+    history: History,
+}
+
+// Two boards are equal when their positions are, whatever moves led there.
+impl PartialEq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        self.board == other.board && self.turn == other.turn && self.rules == other.rules
+    }
+}
+
+impl Eq for Board {}
+
+// The Zobrist key already identifies the position, so there is no need to
+// hash the whole board array.
+impl std::hash::Hash for Board {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+// A list of board array indexes kept inline, so a Move needs no allocation
+// and can be copied. It reads like a slice of the indexes pushed so far.
+#[derive(Clone, Copy, Default)]
+pub struct SquareList {
+    squares: [u8; MAX_MOVE_SQUARES],
+    len: u8,
+}
+
+impl SquareList {
+    pub fn new() -> SquareList {
+        SquareList::default()
+    }
+
+    pub fn push(&mut self, square: u8) {
+        self.squares[self.len as usize] = square;
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(self.squares[self.len as usize])
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl std::ops::Deref for SquareList {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.squares[..self.len as usize]
+    }
+}
+
+impl std::ops::DerefMut for SquareList {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.squares[..self.len as usize]
+    }
+}
+
+// Only the squares pushed so far count, not what is left in the array
+// past them.
+impl PartialEq for SquareList {
+    fn eq(&self, other: &SquareList) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for SquareList {}
+
+impl std::hash::Hash for SquareList {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+impl std::fmt::Debug for SquareList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub struct Move {
-    pub jumps: Vec<u8>,
-    pub captures: Vec<u8>,
-    pub captures_pieces: Vec<u8>,
+    pub jumps: SquareList,
+    pub captures: SquareList,
+    pub captures_pieces: SquareList,
     pub is_king: bool,
     pub color: u8,
+    // the size of the board the move is played on, needed to number its
+    // squares
+    pub board_size: u8,
 }
 
 impl Move {
     pub fn new(color: u8) -> Move {
         Move {
-            jumps: Vec::new(),
-            captures: Vec::new(),
-            captures_pieces: Vec::new(),
+            jumps: SquareList::new(),
+            captures: SquareList::new(),
+            captures_pieces: SquareList::new(),
             is_king: false,
             color,
+            board_size: 8,
         }
     }
 
     pub fn print(&self) {
-        let mut output = String::new();
-        for i in 0..self.jumps.len() {
-            output.push_str(&format!("{} ", self.jumps[i]));
+        println!("{}", self);
+    }
+}
+
+// Moves are written in standard notation: "11-15" for a simple move and
+// "22x15x8" for a capture, listing every square the piece lands on.
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let separator = match self.captures.len() {
+            0 => "-",
+            _ => "x",
+        };
+
+        let geometry = Geometry::new(self.board_size);
+        let squares: Vec<String> = self.jumps.iter().map(|jump| geometry.index_to_number(*jump).to_string()).collect();
+        write!(f, "{}", squares.join(separator))
+    }
+}
+
+// Parsing only knows what the notation says: the color, captured pieces and
+// promotion are left for the board to fill in, see Board::parse_move and
+// Board::check_legal. Every step of a capture has to be written out so the
+// captured squares are known. Without a board the notation is read as 8x8
+// squares.
+impl std::str::FromStr for Move {
+    type Err = CheckersError;
+
+    fn from_str(text: &str) -> Result<Move, CheckersError> {
+        let geometry = Geometry::new(8);
+        let (numbers, capture) = geometry.parse_notation(text)?;
+        let mut m = Move::new(EMPTY);
+
+        for number in numbers {
+            m.jumps.push(geometry.number_to_index(number));
+        }
+
+        if !capture {
+            if m.jumps.len() != 2 {
+                return Err(CheckersError::InvalidNotation(text.to_string()));
+            }
+            return Ok(m);
+        }
+
+        for step in m.jumps.windows(2) {
+            let rows = (step[0] / geometry.size).abs_diff(step[1] / geometry.size);
+            let columns = (step[0] % geometry.size).abs_diff(step[1] % geometry.size);
+            if rows != 2 || columns != 2 {
+                return Err(CheckersError::IncompleteCapture(text.to_string()));
+            }
+            m.captures.push((step[0] + step[1]) / 2);
+        }
+
+        Ok(m)
+    }
+}
+
+// More moves than any position has, kings on an open 10x10 board included.
+// Capture sequences that come to the same move are only added once.
+pub const MAX_MOVES: usize = 256;
+
+// A list of moves kept inline, so generating the moves of a position needs
+// no allocation. A search can keep one per ply and clear it for each node.
+// The unused part of the array is left uninitialized: filling all of it
+// would cost more than the allocation it saves.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [std::mem::MaybeUninit<Move>; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> MoveList {
+        MoveList {
+            moves: [const { std::mem::MaybeUninit::uninit() }; MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, m: Move) {
+        self.moves[self.len].write(m);
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> MoveList {
+        MoveList::new()
+    }
+}
+
+impl std::ops::Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        // SAFETY: the first `len` moves have all been written by push, and
+        // MaybeUninit<Move> has the same layout as Move
+        unsafe { &*(&self.moves[..self.len] as *const [std::mem::MaybeUninit<Move>] as *const [Move]) }
+    }
+}
+
+impl std::ops::DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        // SAFETY: as for deref
+        unsafe { &mut *(&mut self.moves[..self.len] as *mut [std::mem::MaybeUninit<Move>] as *mut [Move]) }
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> std::slice::Iter<'a, Move> {
+        self.iter()
+    }
+}
+
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+// The legal moves of a position handed out one at a time. Captures are all
+// found up front, since the longest has to be known before any can be
+// played, and kept in a list borrowed from the caller. Quiet moves are only
+// made as they are asked for, so a search that stops early skips the rest.
+// It keeps what it needs of the position, so the board can be played on
+// while iterating.
+pub struct Moves<'a> {
+    geometry: Geometry,
+    flying_kings: bool,
+    color: u8,
+    empty: Bitboard,
+    kings: Bitboard,
+    captures: &'a MoveList,
+    next_capture: usize,
+    // pieces whose quiet moves haven't been started on
+    pieces: Bitboard,
+    // the piece whose quiet moves are being handed out, and the squares it
+    // has left to go to
+    from: u8,
+    king: bool,
+    targets: Bitboard,
+}
+
+impl Iterator for Moves<'_> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        if self.next_capture < self.captures.len() {
+            self.next_capture += 1;
+            return Some(self.captures[self.next_capture - 1]);
+        }
+
+        while self.targets == 0 {
+            if self.pieces == 0 {
+                return None;
+            }
+
+            self.from = pop_square(&mut self.pieces);
+            self.king = self.kings & (1 << self.from) != 0;
+            self.targets = self.geometry.quiet_targets(self.from, self.color, self.king, self.flying_kings, self.empty);
+        }
+
+        let to = pop_square(&mut self.targets);
+        Some(self.geometry.quiet_move(self.from, to, self.color, self.king))
+    }
+}
+
+// the most recent moves a board remembers for pop_move, plenty for a
+// search and for taking back moves in a game
+pub const MAX_HISTORY: usize = 128;
+
+// The moves played with push_move, kept in a ring so the board stays a
+// fixed size. Once it is full the oldest move is forgotten to make room.
+#[derive(Clone, Copy)]
+struct History {
+    moves: [Move; MAX_HISTORY],
+    // moves still remembered, the last `len` plies
+    len: usize,
+    ply: u32,
+}
+
+impl History {
+    fn new() -> History {
+        History {
+            moves: [Move::new(EMPTY); MAX_HISTORY],
+            len: 0,
+            ply: 0,
         }
-        println!("{}", output);
+    }
+
+    fn push(&mut self, m: Move) {
+        self.moves[self.ply as usize % MAX_HISTORY] = m;
+        self.ply += 1;
+        self.len = (self.len + 1).min(MAX_HISTORY);
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.ply = 0;
+    }
+
+    fn pop(&mut self) -> Option<Move> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        self.ply -= 1;
+        Some(self.moves[self.ply as usize % MAX_HISTORY])
+    }
+
+    fn iter(&self) -> impl DoubleEndedIterator<Item = &Move> {
+        (self.ply as usize - self.len..self.ply as usize).map(|ply| &self.moves[ply % MAX_HISTORY])
+    }
+}
+
+impl std::fmt::Debug for History {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+fn index_to_square(index: u8) -> u8 {
+    index / 2
+}
+
+fn pop_square(bits: &mut Bitboard) -> u8 {
+    let square = bits.trailing_zeros() as u8;
+    *bits &= *bits - 1;
+    square
+}
+
+pub fn opponent(color: u8) -> u8 {
+    match color {
+        BLACK => WHITE,
+        _ => BLACK,
+    }
+}
+
+fn directions(color: u8, king: bool) -> &'static [i8] {
+    match (color, king) {
+        (_, true) => &ALL_DIRECTIONS,
+        (BLACK, false) => &[UP_LEFT, UP_RIGHT],
+        _ => &[DOWN_LEFT, DOWN_RIGHT],
     }
 }
 
 impl Board {
+    // An American checkers board with no pieces on it, to set up a position
+    // with set_piece.
+    #[allow(dead_code)]
+    pub fn empty() -> Board {
+        Board::empty_with_rules(AMERICAN_RULES)
+    }
+
+    pub fn empty_with_rules(rules: Rules) -> Board {
+        let mut board = Board {
+            board: [EMPTY; MAX_BOARD_SIZE * MAX_BOARD_SIZE],
+            black_pieces_bitboard: 0,
+            white_pieces_bitboard: 0,
+            black_kings_bitboard: 0,
+            white_kings_bitboard: 0,
+            turn: BLACK,
+            hash: 0,
+            rules,
+            geometry: Geometry::new(rules.board_size),
+            history: History::new(),
+        };
+        board.set_side_to_move(rules.first_to_move);
+        board
+    }
+
     pub fn new() -> Board {
-        let mut board = Vec::new();
-        
-        for i in 0..64 {
-            let mut square = EMPTY;
-            if i < 8 {
-                if i % 2 == 1 {
-                    square = WHITE;
-                }
-            } else if i < 16 {
-                if i % 2 == 0 {
-                    square = WHITE;
-                }
-            } else if i < 24 {
-                if i % 2 == 1 {
-                    square = WHITE;
-                }
-            } else if i < 40 {
-                square = EMPTY;
-            } else if i < 48 {
-                if i % 2 == 0 {
-                    square = BLACK;
-                }
-            } else if i < 56 {
-                if i % 2 == 1 {
-                    square = BLACK;
+        Board::with_rules(AMERICAN_RULES)
+    }
+
+    // Sets up the start position of the variant: every row but the middle
+    // two is filled with men, 12 a side on an 8x8 board and 20 on a 10x10.
+    pub fn with_rules(rules: Rules) -> Board {
+        let mut board = Board::empty_with_rules(rules);
+
+        let squares = board.geometry.squares;
+        let men = (board.geometry.row_squares - 1) * board.geometry.row_squares;
+
+        for square in 0..men {
+            board.put_piece(board.geometry.square_to_index(square), WHITE);
+        }
+
+        for square in squares - men..squares {
+            board.put_piece(board.geometry.square_to_index(square), BLACK);
+        }
+
+        board
+    }
+
+    // Reads a position in draughts FEN, e.g. "W:W21,22,K30:B1,2,3". The first
+    // field is the side to move, the others list the pieces of each color by
+    // square number, with a K prefix for kings and "a-b" for a range. FEN
+    // doesn't say which variant it is, so this reads it as American checkers.
+    #[allow(dead_code)]
+    pub fn from_fen(fen: &str) -> Result<Board, CheckersError> {
+        Board::from_fen_with_rules(fen, AMERICAN_RULES)
+    }
+
+    pub fn from_fen_with_rules(fen: &str, rules: Rules) -> Result<Board, CheckersError> {
+        let mut board = Board::empty_with_rules(rules);
+        let geometry = board.geometry;
+        let mut fields = fen.trim().trim_end_matches('.').split(':');
+
+        match fields.next().map(str::trim) {
+            Some("B") => board.set_side_to_move(BLACK),
+            Some("W") => board.set_side_to_move(WHITE),
+            _ => return Err(CheckersError::InvalidFen(format!("no side to move: {}", fen))),
+        }
+
+        for field in fields {
+            let field = field.trim();
+            let color = match field.chars().next() {
+                Some('B') => BLACK,
+                Some('W') => WHITE,
+                _ => return Err(CheckersError::InvalidFen(format!("invalid piece list: {}", field))),
+            };
+
+            for piece in field[1..].split(',') {
+                let piece = piece.trim();
+                if piece.is_empty() {
+                    continue;
                 }
-            } else if i < 64 {
-                if i % 2 == 0 {
-                    square = BLACK;
+
+                let (kind, squares) = match piece.strip_prefix('K') {
+                    Some(squares) => (color | KING, squares),
+                    None => (color, piece),
+                };
+
+                let (first, last) = match squares.split_once('-') {
+                    Some((first, last)) => (geometry.parse_square_number(first)?, geometry.parse_square_number(last)?),
+                    None => (geometry.parse_square_number(squares)?, geometry.parse_square_number(squares)?),
+                };
+
+                for number in first..=last {
+                    let index = geometry.number_to_index(number);
+                    if board.board[index as usize] != EMPTY {
+                        return Err(CheckersError::InvalidFen(format!("square {} is given twice", number)));
+                    }
+                    board.put_piece(index, kind);
                 }
-            } else {
-                square = EMPTY;
             }
-            board.push(square);
         }
 
-        let black_pieces_bitboard: u128 = 0b000000000000000000000000000000000000000000000010101010010101010110101010;
-        let white_pieces_bitboard: u128 = 0b010101011010101001010101000000000000000000000000000000000000000000000000;
-        let black_kings_bitboard: u128 = 0;
-        let white_kings_bitboard: u128 = 0;
+        Ok(board)
+    }
 
-        Board {
-            board,
-            black_pieces_bitboard,
-            white_pieces_bitboard,
-            black_kings_bitboard,
-            white_kings_bitboard,
-            turn: WHITE,
+    pub fn to_fen(self) -> String {
+        let mut fen = String::from(match self.turn {
+            BLACK => "B",
+            _ => "W",
+        });
+
+        for (color, prefix) in [(WHITE, ":W"), (BLACK, ":B")] {
+            let mut squares = Vec::new();
+            for number in 1..=self.geometry.squares {
+                let piece = self.piece_on(self.geometry.number_to_square(number));
+                if piece & color != 0 {
+                    match piece & KING {
+                        0 => squares.push(number.to_string()),
+                        _ => squares.push(format!("K{}", number)),
+                    }
+                }
+            }
+            fen.push_str(prefix);
+            fen.push_str(&squares.join(","));
         }
+
+        fen
     }
 
     pub fn print(&self) {
@@ -121,9 +860,11 @@ impl Board {
             //                     |   | w |   | w |   | w |   |
             //                     -----------------------------
     
-        println!("---------------------------------");
+        let size = self.geometry.size as usize;
+        let line = "-".repeat(size * 4 + 1);
+        println!("{}", line);
 
-        for i in 0..64 {
+        for i in 0..size * size {
             let mut square = " ";
             if self.board[i] & WHITE != 0 {
                 if self.board[i] & KING != 0 {
@@ -139,13 +880,13 @@ impl Board {
                 }
             }
             print!("| {} ", square);
-            if i % 8 == 7 {
+            if i % size == size - 1 {
                 println!("|");
             }
 
-            if i % 8 == 7 {
-                let temp = i - 7;
-                for j in 0..8 {
+            if i % size == size - 1 {
+                let temp = i - (size - 1);
+                for j in 0..size {
                     if temp + j >= 10 {
                         print!("|-{}", temp + j);
                     } else {
@@ -157,540 +898,991 @@ impl Board {
         }
     }
 
-    pub fn search_attack_black(&mut self, from: u8, current_move: Move, moves: &mut HashSet<Move>) {
-        if from > 15 {         
-            if from % 8 > 1 && self.board[from as usize - 9] & WHITE != 0 && self.board[from as usize - 18] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from - 18;
-                new_move.jumps.push(to);
-                new_move.captures.push(from - 9);
-                let captured = self.board[from as usize - 9];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize - 9] = EMPTY;
-                self.board[from as usize - 18] = self.board[from as usize];
-
-                let mut kinged = false;
-                if to < 8 {
-                    if self.board[from as usize - 18] & KING == 0 {
-                        self.board[from as usize - 18] |= KING;
-                        new_move.is_king = true;
-                        kinged = true;
-                    }
-                }
+    fn index_of(&self, number: u8) -> Result<u8, CheckersError> {
+        match (1..=self.geometry.squares).contains(&number) {
+            true => Ok(self.geometry.number_to_index(number)),
+            false => Err(CheckersError::InvalidSquare(number.to_string())),
+        }
+    }
 
-                self.board[from as usize] = EMPTY;
+    // The piece on a square, by its standard number, or EMPTY.
+    pub fn piece_at(&self, number: u8) -> Result<u8, CheckersError> {
+        Ok(self.board[self.index_of(number)? as usize])
+    }
 
-                moves.insert(new_move.clone());
-                self.search_attack_black(to, new_move, moves);
+    // Puts a piece on a square, by its standard number, replacing whatever
+    // was there; EMPTY clears it. Like any other edit this forgets the moves
+    // pop_move could take back, since they led to a different position.
+    pub fn set_piece(&mut self, number: u8, piece: u8) -> Result<(), CheckersError> {
+        let index = self.index_of(number)?;
+        if ![EMPTY, WHITE, BLACK, WHITE_KING, BLACK_KING].contains(&piece) {
+            return Err(CheckersError::InvalidPiece(piece));
+        }
 
-                self.board[from as usize] = self.board[from as usize - 18];
+        self.take_piece(index);
+        if piece != EMPTY {
+            self.put_piece(index, piece);
+        }
+        self.history.clear();
+        Ok(())
+    }
 
-                if kinged { 
-                    self.board[from as usize] &= !KING;
-                }
+    // Takes the piece off a square and returns it.
+    #[allow(dead_code)]
+    pub fn clear_square(&mut self, number: u8) -> Result<u8, CheckersError> {
+        let piece = self.piece_at(number)?;
+        self.set_piece(number, EMPTY)?;
+        Ok(piece)
+    }
+
+    // How many pieces of one kind, e.g. WHITE_KING, are on the board.
+    pub fn piece_count(&self, piece: u8) -> u32 {
+        let bits = match piece {
+            BLACK => self.black_pieces_bitboard & !self.black_kings_bitboard,
+            BLACK_KING => self.black_kings_bitboard,
+            WHITE => self.white_pieces_bitboard & !self.white_kings_bitboard,
+            WHITE_KING => self.white_kings_bitboard,
+            EMPTY => self.empty_squares(),
+            _ => 0,
+        };
+        bits.count_ones()
+    }
 
-                self.board[from as usize - 18] = EMPTY;
-                self.board[from as usize - 9] = captured;
+    // The same position seen from the other side: the board turned round,
+    // the colors of the pieces swapped and the other side to move. Either
+    // side stands exactly as the other did, so anything that plays or scores
+    // both colors alike should treat the two the same.
+    #[allow(dead_code)]
+    pub fn flipped(&self) -> Board {
+        let mut flipped = Board::empty_with_rules(self.rules);
+        flipped.set_side_to_move(opponent(self.turn));
+
+        let squares = self.geometry.squares;
+        for number in 1..=squares {
+            let piece = self.board[self.geometry.number_to_index(number) as usize];
+            if piece != EMPTY {
+                let color = opponent(piece & (WHITE | BLACK));
+                flipped.put_piece(self.geometry.number_to_index(squares + 1 - number), color | (piece & KING));
             }
-            if from % 8 < 6 && self.board[from as usize - 7] & WHITE != 0 && self.board[from as usize - 14] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from - 14;
-                new_move.jumps.push(to);
-                new_move.captures.push(from - 7);
-                let captured = self.board[from as usize - 7];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize - 7] = EMPTY;
-                self.board[from as usize - 14] = self.board[from as usize];
-
-                let mut kinged = false;
-                if to < 8 {
-                    if self.board[from as usize - 14] & KING == 0 {
-                        self.board[from as usize - 14] |= KING;
-                        new_move.is_king = true;
-                        kinged = true;
-                    }
-                }
+        }
 
-                self.board[from as usize] = EMPTY;
+        flipped
+    }
 
-                moves.insert(new_move.clone());
-                self.search_attack_black(to, new_move, moves);
+    fn piece_on(&self, square: u8) -> u8 {
+        let bit = 1 << square;
+        let mut piece = EMPTY;
+        if self.black_pieces_bitboard & bit != 0 {
+            piece = BLACK;
+        } else if self.white_pieces_bitboard & bit != 0 {
+            piece = WHITE;
+        }
+        if (self.black_kings_bitboard | self.white_kings_bitboard) & bit != 0 {
+            piece |= KING;
+        }
+        piece
+    }
 
-                self.board[from as usize] = self.board[from as usize - 14];
+    fn put_piece(&mut self, index: u8, piece: u8) {
+        let square = index_to_square(index);
+        let bit = 1 << square;
+        self.board[index as usize] = piece;
 
-                if kinged {
-                    self.board[from as usize] &= !KING;
-                }
+        if piece != EMPTY {
+            self.hash ^= zobrist_key(square, piece);
+        }
 
-                self.board[from as usize - 14] = EMPTY;
-                self.board[from as usize - 7] = captured;
-            } 
+        if piece & BLACK != 0 {
+            self.black_pieces_bitboard |= bit;
+            if piece & KING != 0 {
+                self.black_kings_bitboard |= bit;
+            }
+        } else if piece & WHITE != 0 {
+            self.white_pieces_bitboard |= bit;
+            if piece & KING != 0 {
+                self.white_kings_bitboard |= bit;
+            }
         }
+    }
 
-        if self.board[from as usize] & KING != 0 && from < 48 {
-            if from % 8 > 1 && self.board[from as usize + 7] & WHITE != 0 && self.board[from as usize + 14] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from + 14;
-                new_move.jumps.push(to);
-                new_move.captures.push(from + 7);
-                let captured = self.board[from as usize + 7];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize + 7] = EMPTY;
-                self.board[from as usize + 14] = self.board[from as usize];
+    fn take_piece(&mut self, index: u8) -> u8 {
+        let square = index_to_square(index);
+        let piece = self.piece_on(square);
+        let mask = !(1 << square);
+        self.board[index as usize] = EMPTY;
 
-                self.board[from as usize] = EMPTY;
+        if piece != EMPTY {
+            self.hash ^= zobrist_key(square, piece);
+        }
 
-                moves.insert(new_move.clone());
-                self.search_attack_black(to, new_move, moves);
+        self.black_pieces_bitboard &= mask;
+        self.white_pieces_bitboard &= mask;
+        self.black_kings_bitboard &= mask;
+        self.white_kings_bitboard &= mask;
 
-                self.board[from as usize] = self.board[from as usize + 14];
+        piece
+    }
 
-                self.board[from as usize + 14] = EMPTY;
-                self.board[from as usize + 7] = captured;
-            }
-            if from % 8 < 6 && self.board[from as usize + 9] & WHITE != 0 && self.board[from as usize + 18] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from + 18;
-                new_move.jumps.push(to);
-                new_move.captures.push(from + 9);
-                let captured = self.board[from as usize + 9];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize + 9] = EMPTY;
-                self.board[from as usize + 18] = self.board[from as usize];
+    fn pieces(&self, color: u8) -> Bitboard {
+        match color {
+            BLACK => self.black_pieces_bitboard,
+            _ => self.white_pieces_bitboard,
+        }
+    }
+
+    fn pieces_mut(&mut self, color: u8) -> &mut Bitboard {
+        match color {
+            BLACK => &mut self.black_pieces_bitboard,
+            _ => &mut self.white_pieces_bitboard,
+        }
+    }
 
-                self.board[from as usize] = EMPTY;
+    fn kings(&self, color: u8) -> Bitboard {
+        match color {
+            BLACK => self.black_kings_bitboard,
+            _ => self.white_kings_bitboard,
+        }
+    }
 
-                moves.insert(new_move.clone());
-                self.search_attack_black(to, new_move, moves);
+    fn empty_squares(&self) -> Bitboard {
+        self.geometry.all & !(self.black_pieces_bitboard | self.white_pieces_bitboard)
+    }
 
-                self.board[from as usize] = self.board[from as usize + 18];
+    fn new_move(&self, color: u8) -> Move {
+        let mut m = Move::new(color);
+        m.board_size = self.geometry.size;
+        m
+    }
 
-                self.board[from as usize + 18] = EMPTY;
-                self.board[from as usize + 9] = captured;
-            }
+    fn capture_directions(&self, color: u8, king: bool) -> &'static [i8] {
+        match king || self.rules.men_capture_backwards {
+            true => &ALL_DIRECTIONS,
+            false => directions(color, false),
         }
     }
 
-    pub fn search_attack_white(&mut self, from: u8, current_move: Move, moves: &mut HashSet<Move>) {
-        if from < 48 {
-            if from % 8 < 6 && self.board[from as usize + 9] & BLACK != 0 && self.board[from as usize + 18] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from + 18;
-                new_move.jumps.push(to);
-                new_move.captures.push(from + 9);
-                let captured = self.board[from as usize + 9];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize + 9] = EMPTY;
-                self.board[from as usize + 18] = self.board[from as usize];
-
-                let mut kinged = false;
-                if to > 55 {
-                    if self.board[from as usize + 18] & KING == 0 {
-                        self.board[from as usize + 18] |= KING;
-                        new_move.is_king = true;
-                        kinged = true;
-                    }
+    // pieces of the given color with at least one capture available
+    fn capturers(&self, color: u8) -> Bitboard {
+        let empty = self.empty_squares();
+        let opponents = self.pieces(opponent(color));
+        let kings = self.kings(color);
+        let mut result = 0;
+
+        for direction in ALL_DIRECTIONS {
+            let movers = match self.capture_directions(color, false).contains(&direction) {
+                true => self.pieces(color),
+                false => kings,
+            };
+
+            // squares a piece has to stand on to jump an opponent piece
+            // going in this direction; flying kings may also stand further
+            // back along an empty diagonal
+            let mut origins = self.geometry.shift(opponents & self.geometry.shift(empty, -direction), -direction);
+            result |= origins & movers;
+
+            if self.rules.flying_kings {
+                while origins & empty != 0 {
+                    origins = self.geometry.shift(origins & empty, -direction);
+                    result |= origins & kings;
                 }
+            }
+        }
+
+        result
+    }
+
+    // The squares a piece lands on when jumping `over` in `direction`. A
+    // flying king may stop on any of the empty squares straight after it.
+    fn landing_squares(&self, over: Bitboard, direction: i8, king: bool) -> Bitboard {
+        let empty = self.empty_squares();
+        let mut to = self.geometry.shift(over, direction) & empty;
+        let mut landings = to;
+
+        if king && self.rules.flying_kings {
+            while to != 0 {
+                to = self.geometry.shift(to, direction) & empty;
+                landings |= to;
+            }
+        }
 
-                self.board[from as usize] = EMPTY;
+        landings
+    }
 
-                moves.insert(new_move.clone());
-                self.search_attack_white(to, new_move, moves);
+    // The opponent piece the piece on `from` would jump going in `direction`,
+    // if any. A flying king may reach it from the far end of an empty
+    // diagonal.
+    fn capture_target(&self, from: u8, color: u8, king: bool, captured: Bitboard, direction: i8) -> Bitboard {
+        let mut over = self.geometry.shift(1 << from, direction);
+        if king && self.rules.flying_kings {
+            while over & self.empty_squares() != 0 {
+                over = self.geometry.shift(over, direction);
+            }
+        }
 
-                self.board[from as usize] = self.board[from as usize + 18];
+        match self.landing_squares(over, direction, false) {
+            0 => 0,
+            _ => over & self.pieces(opponent(color)) & !captured,
+        }
+    }
 
-                if kinged {
-                    self.board[from as usize] &= !KING;
-                }
+    fn can_capture_from(&self, from: u8, color: u8, king: bool, captured: Bitboard) -> bool {
+        self.capture_directions(color, king)
+            .iter()
+            .any(|&direction| self.capture_target(from, color, king, captured, direction) != 0)
+    }
 
-                self.board[from as usize + 18] = EMPTY;
-                self.board[from as usize + 9] = captured;
+    // Walks every capture sequence starting from `from`, pushing the finished
+    // ones into `moves`. Captured pieces stay on the board until the move is
+    // over, so they still block landing squares but can't be jumped twice.
+    fn search_attack(&mut self, from: u8, color: u8, king: bool, captured: Bitboard, current_move: &mut Move, moves: &mut MoveList) {
+        let mut finished = true;
 
+        for &direction in self.capture_directions(color, king) {
+            let over = self.capture_target(from, color, king, captured, direction);
+            if over == 0 {
+                continue;
             }
-            if from % 8 > 1 && self.board[from as usize + 7] & BLACK != 0 && self.board[from as usize + 14] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from + 14;
-                new_move.jumps.push(to);
-                new_move.captures.push(from + 7);
-                let captured = self.board[from as usize + 7];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize + 7] = EMPTY;
-                self.board[from as usize + 14] = self.board[from as usize];
-
-                let mut kinged = false;
-                if to > 55 {
-                    if self.board[from as usize + 14] & KING == 0 {
-                        self.board[from as usize + 14] |= KING;
-                        new_move.is_king = true;
-                        kinged = true;
+
+            finished = false;
+
+            let over_square = over.trailing_zeros() as u8;
+            let mut landings = self.landing_squares(over, direction, king);
+
+            // a king that can carry on capturing from some of its landing
+            // squares has to pick one of those
+            if landings.count_ones() > 1 {
+                let mut continuing = 0;
+                let mut squares = landings;
+                while squares != 0 {
+                    let to = pop_square(&mut squares);
+                    *self.pieces_mut(color) ^= (1 << from) | (1 << to);
+                    if self.can_capture_from(to, color, king, captured | over) {
+                        continuing |= 1 << to;
                     }
+                    *self.pieces_mut(color) ^= (1 << from) | (1 << to);
+                }
+                if continuing != 0 {
+                    landings = continuing;
+                }
+            }
+
+            while landings != 0 {
+                let to_square = pop_square(&mut landings);
+                let to = 1 << to_square;
+                let crowned = !king && to & self.geometry.promotion_row(color) != 0 && self.rules.crowning != Crowning::AtEndOfMove;
+
+                current_move.jumps.push(self.geometry.square_to_index(to_square));
+                current_move.captures.push(self.geometry.square_to_index(over_square));
+                current_move.captures_pieces.push(self.piece_on(over_square));
+                if crowned {
+                    current_move.is_king = true;
                 }
 
-                self.board[from as usize] = EMPTY;
+                *self.pieces_mut(color) ^= (1 << from) | to;
 
-                moves.insert(new_move.clone());
-                self.search_attack_white(to, new_move, moves);
+                if crowned && self.rules.crowning == Crowning::EndsMove {
+                    self.add_capture(*current_move, moves);
+                } else {
+                    self.search_attack(to_square, color, king || crowned, captured | over, current_move, moves);
+                }
 
-                self.board[from as usize] = self.board[from as usize + 14];
+                *self.pieces_mut(color) ^= (1 << from) | to;
 
-                if kinged {
-                    self.board[from as usize] &= !KING;
+                current_move.jumps.pop();
+                current_move.captures.pop();
+                current_move.captures_pieces.pop();
+                if crowned {
+                    current_move.is_king = false;
                 }
+            }
+        }
+
+        if finished && !current_move.captures.is_empty() {
+            let mut finished_move = *current_move;
+            // a man that only passed over the back row is crowned if it
+            // stops there
+            if !king && (1 << from) & self.geometry.promotion_row(color) != 0 {
+                finished_move.is_king = true;
+            }
+            self.add_capture(finished_move, moves);
+        }
+    }
 
-                self.board[from as usize + 14] = EMPTY;
-                self.board[from as usize + 7] = captured;
+    // Adds a finished capture sequence to `moves` as it is found, so that the
+    // list only ever holds distinct legal captures: there can be many times
+    // more sequences than that. Under majority capture only the sequences
+    // taking the most pieces so far are kept.
+    fn add_capture(&self, m: Move, moves: &mut MoveList) {
+        if self.rules.majority_capture {
+            let most = moves.first().map_or(0, |other| other.captures.len());
+            if m.captures.len() < most {
+                return;
+            }
+            if m.captures.len() > most {
+                moves.clear();
             }
         }
 
-        if self.board[from as usize] & KING != 0 && from > 15 {
-            if from % 8 < 6 && self.board[from as usize - 7] & BLACK != 0 && self.board[from as usize - 14] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from - 14;
-                new_move.jumps.push(to);
-                new_move.captures.push(from - 7);
-                let captured = self.board[from as usize - 7];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize - 7] = EMPTY;
-                self.board[from as usize - 14] = self.board[from as usize];
+        // a capture starting and finishing on the same squares and taking
+        // the same pieces as another, e.g. going round a ring of pieces the
+        // other way, is the same move
+        let key = |m: &Move| {
+            let captured = m.captures.iter().fold(0 as Bitboard, |bits, capture| bits | 1 << index_to_square(*capture));
+            (m.jumps[0], m.jumps[m.jumps.len() - 1], captured)
+        };
+        if !moves.iter().any(|other| key(other) == key(&m)) {
+            moves.push(m);
+        }
+    }
 
-                self.board[from as usize] = EMPTY;
+    fn search_captures(&mut self, from: u8, color: u8, moves: &mut MoveList) {
+        let mut current_move = self.new_move(color);
+        current_move.jumps.push(self.geometry.square_to_index(from));
 
-                moves.insert(new_move.clone());
-                self.search_attack_white(to, new_move, moves);
+        let king = self.kings(color) & (1 << from) != 0;
+        self.search_attack(from, color, king, 0, &mut current_move, moves);
+    }
 
-                self.board[from as usize] = self.board[from as usize - 14];
+    // Writes every legal move of `color` into `moves`, replacing what was
+    // there.
+    fn search_all(&mut self, color: u8, moves: &mut MoveList) {
+        moves.clear();
 
-                if to > 55 {
-                    self.board[from as usize] &= !KING;
-                }
+        let capturers = self.capturers(color);
+        match capturers {
+            0 => self.search_quiet(color, moves),
+            _ => self.search_all_captures(color, capturers, moves),
+        }
+    }
 
-                self.board[from as usize - 14] = EMPTY;
-                self.board[from as usize - 7] = captured;
+    fn search_all_captures(&mut self, color: u8, mut capturers: Bitboard, moves: &mut MoveList) {
+        while capturers != 0 {
+            let from = pop_square(&mut capturers);
+            self.search_captures(from, color, moves);
+        }
+    }
+
+    fn search_quiet(&self, color: u8, moves: &mut MoveList) {
+        let mut pieces = self.pieces(color);
+        while pieces != 0 {
+            let from = pop_square(&mut pieces);
+            let king = self.kings(color) & (1 << from) != 0;
+
+            let mut targets = self.geometry.quiet_targets(from, color, king, self.rules.flying_kings, self.empty_squares());
+            while targets != 0 {
+                let to = pop_square(&mut targets);
+                moves.push(self.geometry.quiet_move(from, to, color, king));
             }
-            if from % 8 > 1 && self.board[from as usize - 9] & BLACK != 0 && self.board[from as usize - 18] == EMPTY {
-                let mut new_move = current_move.clone();
-                let to = from - 18;
-                new_move.jumps.push(to);
-                new_move.captures.push(from - 9);
-                let captured = self.board[from as usize - 9];
-                new_move.captures_pieces.push(captured);
-                self.board[from as usize - 9] = EMPTY;
-                self.board[from as usize - 18] = self.board[from as usize];
+        }
+    }
 
-                self.board[from as usize] = EMPTY;
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
 
-                moves.insert(new_move.clone());
-                self.search_attack_white(to, new_move, moves);
+    // Only rules for the same board size make sense for the pieces already
+    // on the board.
+    #[allow(dead_code)]
+    pub fn set_rules(&mut self, rules: Rules) {
+        debug_assert_eq!(rules.board_size, self.rules.board_size);
+        self.rules = rules;
+    }
 
-                self.board[from as usize] = self.board[from as usize - 18];
+    pub fn side_to_move(&self) -> u8 {
+        self.turn
+    }
 
-                if to > 55 {
-                    self.board[from as usize] &= !KING;
-                }
+    pub fn set_side_to_move(&mut self, color: u8) {
+        if self.turn != color {
+            self.switch_turn();
+        }
+    }
 
-                self.board[from as usize - 18] = EMPTY;
-                self.board[from as usize - 9] = captured;
-            }
+    fn switch_turn(&mut self) {
+        self.turn = opponent(self.turn);
+        self.hash ^= ZOBRIST_WHITE_TO_MOVE;
+    }
+
+    pub fn hash_key(&self) -> u64 {
+        self.hash
+    }
+
+    fn compute_hash_key(&self) -> u64 {
+        let mut hash = 0;
+        let mut occupied = self.black_pieces_bitboard | self.white_pieces_bitboard;
+        while occupied != 0 {
+            let square = pop_square(&mut occupied);
+            hash ^= zobrist_key(square, self.piece_on(square));
+        }
+
+        if self.turn == WHITE {
+            hash ^= ZOBRIST_WHITE_TO_MOVE;
         }
+
+        hash
     }
 
-    pub fn search_black(&mut self, from: u8) -> Vec<Move> {
-        let mut moves = HashSet::new();
-        let mut current_move = Move::new(BLACK);
-        current_move.jumps.push(from);
+    pub fn legal_moves(&mut self) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        self.search_all(self.turn, &mut moves);
+        moves.to_vec()
+    }
 
-        if from > 7 && from % 8 > 0 {
-            if self.board[from as usize - 9] == EMPTY {
-                let mut new_move = current_move.clone();
-                new_move.jumps.push(from - 9);
-                if from < 16 && self.board[from as usize] & KING == 0 {
-                    new_move.is_king = true;
-                }
-                moves.insert(new_move);
-            }
-            else if self.board[from as usize - 9] & WHITE != 0 && from % 8 > 1 {
-                self.search_attack_black(from, current_move.clone(), &mut moves);
-            }
+    // Writes the legal moves into `moves`, replacing what was there, without
+    // allocating.
+    pub fn generate_moves(&mut self, moves: &mut MoveList) {
+        self.search_all(self.turn, moves);
+    }
+
+    // The legal moves, generated lazily; see Moves. Whatever `captures` held
+    // is replaced.
+    #[allow(dead_code)]
+    pub fn moves<'a>(&self, captures: &'a mut MoveList) -> Moves<'a> {
+        let mut pieces = self.pieces(self.turn);
+
+        captures.clear();
+        let capturers = self.capturers(self.turn);
+        if capturers != 0 {
+            let mut board = *self;
+            board.search_all_captures(self.turn, capturers, captures);
+            pieces = 0;
         }
 
-        if from > 7 && from % 8 < 7 {
-            if self.board[from as usize - 7] == EMPTY {
-                let mut new_move = current_move.clone();
-                new_move.jumps.push(from - 7);
-                if from < 16 && self.board[from as usize] & KING == 0 {
-                    new_move.is_king = true;
-                }
-                moves.insert(new_move);
-            }
-            else if self.board[from as usize - 7] & WHITE != 0 && from % 8 < 6 {
-                self.search_attack_black(from, current_move.clone(), &mut moves);
-            }
+        Moves {
+            geometry: self.geometry,
+            flying_kings: self.rules.flying_kings,
+            color: self.turn,
+            empty: self.empty_squares(),
+            kings: self.kings(self.turn),
+            captures,
+            next_capture: 0,
+            pieces,
+            from: 0,
+            king: false,
+            targets: 0,
         }
+    }
 
-        if self.board[from as usize] & KING != 0 {
-            if from < 56 && from % 8 > 0 {
-                if self.board[from as usize + 7] == EMPTY {
-                    let mut new_move = current_move.clone();
-                    new_move.jumps.push(from + 7);
-                    moves.insert(new_move);
-                }
-                else if self.board[from as usize + 7] & WHITE != 0 && from % 8 > 1 {
-                    self.search_attack_black(from, current_move.clone(), &mut moves);
-                }
-            }
+    // Counts the positions reached after `depth` plies, to check the move
+    // generator against known numbers.
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        let mut lists = vec![MoveList::new(); depth as usize];
+        self.perft_with(depth, &mut lists)
+    }
 
-            if from < 56 && from % 8 < 7 {
-                if self.board[from as usize + 9] == EMPTY {
-                    let mut new_move = current_move.clone();
-                    new_move.jumps.push(from + 9);
-                    moves.insert(new_move);
-                }
-                else if self.board[from as usize + 9] & WHITE != 0 && from % 8 < 6 {
-                    self.search_attack_black(from, current_move.clone(), &mut moves);
-                }
-            }
+    // perft with a move list for each ply to come, so nothing is allocated
+    // or cleared on the way down
+    fn perft_with(&mut self, depth: u32, lists: &mut [MoveList]) -> u64 {
+        if depth == 0 {
+            return 1;
         }
 
-        let mut result = Vec::new();
+        let (moves, rest) = lists.split_first_mut().expect("a move list for every ply");
+        self.generate_moves(moves);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
 
-        let mut moves_with_attacks = Vec::new();
+        let mut nodes = 0;
         for m in moves.iter() {
-            if m.captures.len() > 0 {
-                moves_with_attacks.push(m.clone());
-            }
+            self.make_move(m);
+            nodes += self.perft_with(depth - 1, rest);
+            self.undo_move(m);
         }
 
-        if moves_with_attacks.len() > 0 {
-            for m in moves_with_attacks {
-                result.push(m);
-            }
+        nodes
+    }
 
-            let mut max_captures = 0;
-            for m in &result {
-                if m.captures.len() > max_captures {
-                    max_captures = m.captures.len();
-                }
+    // perft split up by the first move, to narrow down where two move
+    // generators disagree
+    pub fn divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        let mut counts = Vec::new();
+        for m in self.legal_moves() {
+            self.make_move(&m);
+            let nodes = self.perft(depth.saturating_sub(1));
+            self.undo_move(&m);
+            counts.push((m, nodes));
+        }
+
+        counts
+    }
+
+    // Resolves a move in standard notation against the legal moves. Besides
+    // the full form, a capture may be given by just its first and last square
+    // as long as that picks out a single legal move.
+    pub fn parse_move(&mut self, text: &str) -> Result<Move, CheckersError> {
+        let (numbers, capture) = self.geometry.parse_notation(text)?;
+
+        let mut matches = Vec::new();
+        for m in self.legal_moves() {
+            if m.captures.is_empty() == capture {
+                continue;
             }
 
-            let mut i = 0;
-            while i < result.len() {
-                if result[i].captures.len() < max_captures {
-                    result.remove(i);
-                }
-                else {
-                    i += 1;
-                }
+            let squares: Vec<u8> = m.jumps.iter().map(|jump| self.geometry.index_to_number(*jump)).collect();
+            let short_form = numbers.len() == 2 && squares[0] == numbers[0] && squares[squares.len() - 1] == numbers[1];
+            if squares == numbers || short_form {
+                matches.push(m);
             }
         }
-        else {
-            for m in moves {
-                result.push(m);
-            }
+
+        match matches.len() {
+            0 => Err(CheckersError::IllegalMove(text.trim().to_string())),
+            1 => Ok(matches.remove(0)),
+            _ => Err(CheckersError::AmbiguousMove(text.trim().to_string())),
         }
+    }
 
-        result
+    #[allow(dead_code)]
+    pub fn search_black_all(&mut self) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        self.search_all(BLACK, &mut moves);
+        moves.to_vec()
     }
 
-    pub fn search_white(&mut self, from: u8) -> Vec<Move> {
-        let mut moves = HashSet::new();
-        let mut current_move = Move::new(WHITE);
-        current_move.jumps.push(from);
+    #[allow(dead_code)]
+    pub fn search_white_all(&mut self) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        self.search_all(WHITE, &mut moves);
+        moves.to_vec()
+    }
 
-        if from < 56 && from % 8 > 0 {
-            if self.board[from as usize + 7] == EMPTY {
-                let mut new_move = current_move.clone();
-                new_move.jumps.push(from + 7);
-                if from > 47 && self.board[from as usize] & KING == 0 {
-                    new_move.is_king = true;
-                }
-                moves.insert(new_move);
-            }
-            else if self.board[from as usize + 7] & BLACK != 0 && from % 8 > 1 {
-                self.search_attack_white(from, current_move.clone(), &mut moves);
-            }
+    // make_move trusts the move it is given, this checks first that the move
+    // can be played on this board at all so a bad one is turned away instead
+    // of corrupting the board. It doesn't check that the move is legal.
+    #[allow(dead_code)]
+    pub fn try_make_move(&mut self, move_to_make: &Move) -> Result<(), CheckersError> {
+        self.check_move(move_to_make)?;
+        self.make_move(move_to_make);
+        Ok(())
+    }
+
+    fn check_move(&self, m: &Move) -> Result<(), CheckersError> {
+        let invalid = |reason: &str| -> Result<(), CheckersError> { Err(CheckersError::InvalidMove(reason.to_string())) };
+
+        if m.board_size != self.geometry.size {
+            return invalid("it is for another board size");
         }
 
-        if from < 56 && from % 8 < 7 {
-            if self.board[from as usize + 9] == EMPTY {
-                let mut new_move = current_move.clone();
-                new_move.jumps.push(from + 9);
-                if from > 47 && self.board[from as usize] & KING == 0 {
-                    new_move.is_king = true;
-                }
-                moves.insert(new_move);
-            }
-            else if self.board[from as usize + 9] & BLACK != 0 && from % 8 < 6 {
-                self.search_attack_white(from, current_move.clone(), &mut moves);
-            }
+        if m.jumps.len() < 2 {
+            return invalid("it needs a start and an end square");
         }
 
-        if self.board[from as usize] & KING != 0 {
-            if from > 7 && from % 8 > 0 {
-                if self.board[from as usize - 9] == EMPTY {
-                    let mut new_move = current_move.clone();
-                    new_move.jumps.push(from - 9);
-                    moves.insert(new_move);
-                }
-                else if self.board[from as usize - 9] & BLACK != 0 && from % 8 > 1 {
-                    self.search_attack_white(from, current_move.clone(), &mut moves);
-                }
-            }
+        let playable = |index: &u8| *index < self.geometry.size * self.geometry.size && self.geometry.square_to_index(index_to_square(*index)) == *index;
+        if !m.jumps.iter().chain(m.captures.iter()).all(playable) {
+            return invalid("it goes through a square that isn't playable");
+        }
 
-            if from > 7 && from % 8 < 7 {
-                if self.board[from as usize - 7] == EMPTY {
-                    let mut new_move = current_move.clone();
-                    new_move.jumps.push(from - 7);
-                    moves.insert(new_move);
-                }
-                else if self.board[from as usize - 7] & BLACK != 0 && from % 8 < 6 {
-                    self.search_attack_white(from, current_move.clone(), &mut moves);
-                }
-            }
+        let from = m.jumps[0];
+        let to = m.jumps[m.jumps.len() - 1];
+        if self.board[from as usize] & self.turn == 0 {
+            return invalid("it doesn't start on a piece of the side to move");
         }
 
-        let mut result = Vec::new();
+        if to != from && self.board[to as usize] != EMPTY {
+            return invalid("it ends on an occupied square");
+        }
 
-        let mut moves_with_attacks = Vec::new();
-        for m in moves.iter() {
-            if m.captures.len() > 0 {
-                moves_with_attacks.push(m.clone());
-            }
+        if m.captures_pieces.len() != m.captures.len() {
+            return invalid("every captured piece has to be given");
         }
 
-        if moves_with_attacks.len() > 0 {
-            for m in moves_with_attacks {
-                result.push(m);
+        for (i, (capture, piece)) in m.captures.iter().zip(m.captures_pieces.iter()).enumerate() {
+            let on_board = self.board[*capture as usize];
+            if on_board & opponent(self.turn) == 0 || on_board != *piece || m.captures[..i].contains(capture) {
+                return invalid("it captures something other than an opponent piece");
             }
+        }
 
-            let mut max_captures = 0;
-            for m in &result {
-                if m.captures.len() > max_captures {
-                    max_captures = m.captures.len();
-                }
-            }
+        Ok(())
+    }
 
-            let mut i = 0;
-            while i < result.len() {
-                if result[i].captures.len() < max_captures {
-                    result.remove(i);
-                }
-                else {
-                    i += 1;
-                }
-            }
+    #[allow(dead_code)]
+    pub fn is_legal(&mut self, m: &Move) -> bool {
+        self.check_legal(m).is_ok()
+    }
+
+    // Plays the move with push_move only if it is legal, returning the legal
+    // move played, otherwise says why it isn't.
+    pub fn make_move_checked(&mut self, move_to_make: &Move) -> Result<Move, CheckersError> {
+        let legal = self.check_legal(move_to_make)?;
+        self.push_move(&legal);
+        Ok(legal)
+    }
+
+    // A move is legal when it is one of the generated moves, jump for jump
+    // and taking the same squares. The legal move is returned, as the move
+    // given may leave the color, captured pieces and crowning out, e.g. when
+    // it was parsed from notation. Crowning is only checked when it was set.
+    // When the move isn't legal the generated moves are compared against it
+    // to give a reason.
+    fn check_legal(&mut self, m: &Move) -> Result<Move, CheckersError> {
+        let illegal = |reason: &str| -> Result<Move, CheckersError> { Err(CheckersError::IllegalMove(format!("{}, {}", m, reason))) };
+
+        if m.board_size != self.geometry.size {
+            return Err(CheckersError::InvalidMove("it is for another board size".to_string()));
         }
-        else {
-            for m in moves {
-                result.push(m);
-            }
+
+        if m.color != EMPTY && m.color != self.turn {
+            return illegal("it isn't that side's move");
         }
 
-        result
-    }
+        let captured = |m: &Move| {
+            let mut captures = m.captures;
+            captures.sort();
+            captures
+        };
+
+        let legal_moves = self.legal_moves();
+        if let Some(legal) = legal_moves.iter().find(|legal| legal.jumps == m.jumps && captured(legal) == captured(m)) {
+            return match m.is_king && !legal.is_king {
+                true => illegal("the piece can't be crowned"),
+                false => Ok(*legal),
+            };
+        }
 
-    pub fn search_black_all(&mut self) -> Vec<Move> {
-        let mut moves = Vec::new();
-        for i in 0..64 {
-            if self.board[i] & BLACK != 0 {
-                let mut m = self.search_black(i as u8);
-                moves.append(&mut m);
-            }
+        if m.captures.is_empty() && legal_moves.iter().any(|legal| !legal.captures.is_empty()) {
+            return illegal("a capture has to be taken");
         }
 
-        let mut result = Vec::new();
-        // if there are moves with captures, only keep those
-        let mut captures = false;
-        for m in &moves {
-            if m.captures.len() > 0 {
-                captures = true;
-                break;
-            }
+        if legal_moves.iter().any(|legal| legal.jumps.len() > m.jumps.len() && legal.jumps.starts_with(&m.jumps)) {
+            return illegal("the capture has to carry on");
         }
 
-        if captures {
-            for m in moves {
-                if m.captures.len() > 0 {
-                    result.push(m);
-                }
+        if self.rules.majority_capture {
+            let rules = self.rules;
+            self.rules.majority_capture = false;
+            let any_capture = self.legal_moves().iter().any(|legal| legal.jumps == m.jumps);
+            self.rules = rules;
+
+            if any_capture {
+                return illegal("a capture taking more pieces has to be chosen");
             }
         }
-        else {
-            for m in moves {
-                result.push(m);
-            }
-        } 
 
-        result
+        illegal("the piece can't move that way")
     }
 
-    pub fn search_white_all(&mut self) -> Vec<Move> {
-        let mut moves = Vec::new();
-        for i in 0..64 {
-            if self.board[i] & WHITE != 0 {
-                let mut m = self.search_white(i as u8);
-                moves.append(&mut m);
-            }
+    pub fn make_move(&mut self, move_to_make: &Move) {
+        let from = move_to_make.jumps[0];
+        let to = move_to_make.jumps[move_to_make.jumps.len() - 1];
+
+        let mut piece = self.take_piece(from);
+
+        for capture in move_to_make.captures.iter() {
+            self.take_piece(*capture);
         }
 
-        let mut result = Vec::new();
-        // if there are moves with captures, only keep those
-        let mut captures = false;
-        for m in &moves {
-            if m.captures.len() > 0 {
-                captures = true;
-                break;
-            }
+        if move_to_make.is_king {
+            piece |= KING;
         }
 
-        if captures {
-            for m in moves {
-                if m.captures.len() > 0 {
-                    result.push(m);
-                }
-            }
+        self.put_piece(to, piece);
+
+        self.switch_turn();
+
+        debug_assert_eq!(self.hash, self.compute_hash_key());
+    }
+
+    pub fn undo_move(&mut self, move_to_undo: &Move) {
+        self.switch_turn();
+
+        let from = move_to_undo.jumps[0];
+        let to = move_to_undo.jumps[move_to_undo.jumps.len() - 1];
+
+        let mut piece = self.take_piece(to);
+
+        if move_to_undo.is_king {
+            piece &= !KING;
         }
-        else {
-            for m in moves {
-                result.push(m);
-            }
-        } 
 
+        self.put_piece(from, piece);
+
+        for (capture, captured_piece) in move_to_undo.captures.iter().zip(move_to_undo.captures_pieces.iter()) {
+            self.put_piece(*capture, *captured_piece);
+        }
+
+        debug_assert_eq!(self.hash, self.compute_hash_key());
+    }
+
+    // Plays the move and remembers it, so pop_move can take it back without
+    // being handed it again.
+    pub fn push_move(&mut self, m: &Move) {
+        self.make_move(m);
+        self.history.push(*m);
+    }
+
+    // Takes back the last move played with push_move and returns it, or None
+    // when there is none left to take back. Only the last MAX_HISTORY moves
+    // are remembered.
+    #[must_use]
+    pub fn pop_move(&mut self) -> Option<Move> {
+        let m = self.history.pop()?;
+        self.undo_move(&m);
+        Some(m)
+    }
+
+    // The moves pop_move can still take back, oldest first.
+    #[allow(dead_code)]
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &Move> {
+        self.history.iter()
+    }
+
+    // Moves played with push_move since the board was set up, less the ones
+    // taken back.
+    pub fn ply(&self) -> u32 {
+        self.history.ply
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(reason: &str) -> Result<(), CheckersError> {
+        Err(CheckersError::InvalidMove(reason.to_string()))
+    }
+
+    // Plays `m` with try_make_move, checking that a rejected move leaves
+    // the board as it was.
+    fn try_move(board: &Board, m: &Move) -> Result<(), CheckersError> {
+        let mut played = *board;
+        let result = played.try_make_move(m);
+        if result.is_err() {
+            assert_eq!(&played, board);
+        }
         result
     }
 
-    pub fn make_move(&mut self, move_to_make: &Move) {
-        let mut from = move_to_make.jumps[0];
-        for i in 1..move_to_make.jumps.len() {
-            let to = move_to_make.jumps[i];
-            self.board[to as usize] = self.board[from as usize];
-            self.board[from as usize] = EMPTY;
-            from = to;
+    #[test]
+    fn try_make_move_checks_the_move() {
+        let board = Board::new();
+        let moves = board.clone().legal_moves();
+        assert_eq!(try_move(&board, &moves[0]), Ok(()));
+
+        let mut m = moves[0];
+        m.board_size = 10;
+        assert_eq!(try_move(&board, &m), invalid("it is for another board size"));
+
+        let mut m = moves[0];
+        m.jumps.pop();
+        assert_eq!(try_move(&board, &m), invalid("it needs a start and an end square"));
+
+        // index 0 is a light square, and 200 is off the board
+        for index in [0, 200] {
+            let mut m = moves[0];
+            m.jumps[1] = index;
+            assert_eq!(try_move(&board, &m), invalid("it goes through a square that isn't playable"));
         }
 
-        for i in 0..move_to_make.captures.len() {
-            self.board[move_to_make.captures[i] as usize] = EMPTY;
+        let mut white = board;
+        white.set_side_to_move(WHITE);
+        let white_move = white.legal_moves().remove(0);
+        assert_eq!(try_move(&board, &white_move), invalid("it doesn't start on a piece of the side to move"));
+
+        // onto the square another black piece starts from
+        let mut m = moves[0];
+        let other = moves.iter().find(|other| other.jumps[0] != m.jumps[0]).unwrap();
+        m.jumps[1] = other.jumps[0];
+        assert_eq!(try_move(&board, &m), invalid("it ends on an occupied square"));
+    }
+
+    #[test]
+    fn try_make_move_checks_the_captures() {
+        let board = Board::from_fen("B:W18:B14").unwrap();
+        let capture = board.clone().legal_moves().remove(0);
+        assert_eq!(capture.captures.len(), 1);
+
+        let mut m = capture;
+        m.captures_pieces.clear();
+        assert_eq!(try_move(&board, &m), invalid("every captured piece has to be given"));
+
+        // a king where there is a man
+        let mut m = capture;
+        m.captures_pieces[0] |= KING;
+        assert_eq!(try_move(&board, &m), invalid("it captures something other than an opponent piece"));
+
+        // the capturing piece itself
+        let mut m = capture;
+        m.captures[0] = m.jumps[0];
+        m.captures_pieces[0] = BLACK;
+        assert_eq!(try_move(&board, &m), invalid("it captures something other than an opponent piece"));
+
+        let mut m = capture;
+        m.captures.push(m.captures[0]);
+        m.captures_pieces.push(m.captures_pieces[0]);
+        assert_eq!(try_move(&board, &m), invalid("it captures something other than an opponent piece"));
+
+        let mut played = board;
+        assert_eq!(played.try_make_move(&capture), Ok(()));
+        assert_eq!(played.to_fen(), "W:W:B23");
+    }
+
+    fn illegal(text: &str, reason: &str) -> Result<Move, CheckersError> {
+        Err(CheckersError::IllegalMove(format!("{}, {}", text, reason)))
+    }
+
+    // Plays the notation with make_move_checked, checking that a rejected
+    // move leaves the board as it was and that is_legal agrees.
+    fn checked(board: &Board, text: &str) -> Result<Move, CheckersError> {
+        let m: Move = text.parse().unwrap();
+        let mut played = *board;
+        assert_eq!(played.is_legal(&m), played.clone().make_move_checked(&m).is_ok());
+
+        let result = played.make_move_checked(&m);
+        if result.is_err() {
+            assert_eq!(&played, board);
         }
+        result
+    }
 
-        if move_to_make.is_king {
-            self.board[from as usize] = self.board[from as usize] | KING;
+    #[test]
+    fn make_move_checked_plays_legal_moves() {
+        let board = Board::new();
+        let m = checked(&board, "11-15").unwrap();
+        assert_eq!(m.color, BLACK);
+
+        let mut played = board;
+        played.make_move(&m);
+        let mut checked_board = board;
+        checked_board.make_move_checked(&m).unwrap();
+        assert_eq!(checked_board, played);
+
+        // the captured piece is filled in from the board
+        let board = Board::from_fen("B:W18:B14").unwrap();
+        let m = checked(&board, "14x23").unwrap();
+        assert_eq!(m.captures_pieces[..], [WHITE]);
+
+        // and so is crowning
+        let board = Board::from_fen("B:W:B27").unwrap();
+        assert!(checked(&board, "27-32").unwrap().is_king);
+    }
+
+    #[test]
+    fn make_move_checked_says_why_a_move_is_illegal() {
+        let board = Board::new();
+        assert_eq!(checked(&board, "9-18"), illegal("9-18", "the piece can't move that way"));
+        assert_eq!(checked(&board, "22-18"), illegal("22-18", "the piece can't move that way"));
+
+        let mut white = board;
+        white.set_side_to_move(WHITE);
+        let white_move = white.legal_moves().remove(0);
+        assert_eq!(board.clone().make_move_checked(&white_move), illegal(&white_move.to_string(), "it isn't that side's move"));
+
+        let mut king = board.clone().legal_moves().remove(0);
+        king.is_king = true;
+        assert_eq!(board.clone().make_move_checked(&king), illegal(&king.to_string(), "the piece can't be crowned"));
+
+        let mut m: Move = "11-15".parse().unwrap();
+        m.board_size = 10;
+        assert_eq!(board.clone().make_move_checked(&m), Err(CheckersError::InvalidMove("it is for another board size".to_string())));
+
+        let board = Board::from_fen("B:W18:B14,1").unwrap();
+        assert_eq!(checked(&board, "1-5"), illegal("1-5", "a capture has to be taken"));
+
+        let board = Board::from_fen("B:W18,27:B14").unwrap();
+        assert_eq!(checked(&board, "14x23"), illegal("14x23", "the capture has to carry on"));
+        assert!(checked(&board, "14x23x32").is_ok());
+
+        // with American rules either capture may be chosen
+        let board = Board::from_fen("B:W18,27,19:B14,15").unwrap();
+        assert!(checked(&board, "15x22").is_ok());
+        let mut majority = board;
+        majority.set_rules(MAJORITY_CAPTURE_RULES);
+        assert_eq!(checked(&majority, "15x22"), illegal("15x22", "a capture taking more pieces has to be chosen"));
+        assert!(checked(&majority, "14x23x32").is_ok());
+    }
+
+    #[test]
+    fn pop_move_takes_back_push_move() {
+        let start = Board::new();
+        let mut board = start;
+        let mut played = Vec::new();
+        for _ in 0..10 {
+            let m = board.legal_moves()[0];
+            board.push_move(&m);
+            played.push(m);
         }
+        assert_eq!(board.ply(), 10);
+        assert!(board.history().eq(played.iter()));
+
+        while let Some(m) = board.pop_move() {
+            assert_eq!(Some(m), played.pop());
+            assert_eq!(board.hash_key(), board.compute_hash_key());
+        }
+        assert!(played.is_empty());
+        assert_eq!(board, start);
+        assert_eq!(board.hash_key(), start.hash_key());
+        assert_eq!(board.ply(), 0);
     }
 
-    pub fn undo_move(&mut self, move_to_undo: &Move) {
-        let mut from = move_to_undo.jumps[move_to_undo.jumps.len() - 1];
-        for i in (1..move_to_undo.jumps.len()).rev() {
-            let to = move_to_undo.jumps[i - 1];
-            self.board[to as usize] = self.board[from as usize];
-            self.board[from as usize] = EMPTY;
-            from = to;
+    #[test]
+    fn history_keeps_the_last_moves() {
+        let mut board = Board::from_fen("B:WK32:BK1").unwrap();
+        let plies = MAX_HISTORY + 4;
+        for ply in 0..plies {
+            let m = board.parse_move(["1-6", "32-27", "6-1", "27-32"][ply % 4]).unwrap();
+            board.push_move(&m);
         }
+        assert_eq!(board.ply() as usize, plies);
+        assert_eq!(board.history().count(), MAX_HISTORY);
 
-        for i in 0..move_to_undo.captures.len() {
-            self.board[move_to_undo.captures[i] as usize] = move_to_undo.captures_pieces[i];
+        for _ in 0..MAX_HISTORY {
+            assert!(board.pop_move().is_some());
         }
+        // the first moves were forgotten to make room
+        assert_eq!(board.pop_move(), None);
+        assert_eq!(board.ply(), 4);
+    }
 
-        if move_to_undo.is_king {
-            self.board[from as usize] = self.board[from as usize] & !KING;
+    #[test]
+    fn set_piece_edits_the_position() {
+        let mut board = Board::new();
+        let m = board.legal_moves()[0];
+        board.push_move(&m);
+
+        board.set_piece(15, WHITE_KING).unwrap();
+        board.set_piece(1, EMPTY).unwrap();
+        assert_eq!(board.piece_at(15), Ok(WHITE_KING));
+        assert_eq!(board.piece_at(1), Ok(EMPTY));
+        assert_eq!(board.hash_key(), board.compute_hash_key());
+        // the move pushed led to another position
+        assert_eq!(board.pop_move(), None);
+    }
+
+    #[test]
+    fn set_piece_rejects_bad_input() {
+        let start = Board::new();
+        let mut board = start;
+
+        for piece in [WHITE | BLACK, KING, 8, 255] {
+            assert_eq!(board.set_piece(15, piece), Err(CheckersError::InvalidPiece(piece)));
+        }
+        for number in [0, 33, 50] {
+            assert_eq!(board.set_piece(number, BLACK), Err(CheckersError::InvalidSquare(number.to_string())));
+            assert_eq!(board.piece_at(number), Err(CheckersError::InvalidSquare(number.to_string())));
+        }
+        assert_eq!(board, start);
+
+        // the squares go up to 50 on a 10x10 board
+        let mut board = Board::with_rules(INTERNATIONAL_RULES);
+        assert_eq!(board.set_piece(50, BLACK_KING), Ok(()));
+        assert_eq!(board.set_piece(51, BLACK_KING), Err(CheckersError::InvalidSquare("51".to_string())));
+    }
+
+    #[test]
+    fn flipped_boards_score_the_same() {
+        let boards = [
+            Board::new(),
+            Board::from_fen("B:W14,18,23,27,K32,K9:B5,10,12,K20,K26,21").unwrap(),
+            Board::from_fen("W:W6,7,K19:B2,3,10,11,K27,K28,16").unwrap(),
+            Board::from_fen_with_rules("W:W7,8,K28,33,39,44,K46:B12,13,19,K23,24,29,K35,40", INTERNATIONAL_RULES).unwrap(),
+        ];
+
+        for board in boards {
+            let flipped = board.flipped();
+            assert_eq!(flipped.flipped(), board);
+            assert_eq!(crate::search::evaluate(&flipped), crate::search::evaluate(&board));
         }
     }
 }
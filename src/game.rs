@@ -0,0 +1,307 @@
+use crate::checkers;
+
+// Why a game ended.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Reason {
+    // the loser has no pieces left
+    NoPiecesLeft,
+    // the loser has pieces but none of them can move
+    NoMovesLeft,
+    // the same position came up three times with the same side to move
+    ThreefoldRepetition,
+    // neither side captured or moved a man for the set number of moves
+    NoProgress,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum GameResult {
+    // the color that won, and how
+    Win(u8, Reason),
+    Draw(Reason),
+}
+
+impl std::fmt::Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let color_name = |color: u8| match color {
+            checkers::BLACK => "Black",
+            _ => "White",
+        };
+
+        match self {
+            GameResult::Win(winner, reason) => {
+                let loser = color_name(checkers::opponent(*winner));
+                match reason {
+                    Reason::NoPiecesLeft => write!(f, "{} wins, {} has no pieces left", color_name(*winner), loser),
+                    _ => write!(f, "{} wins, {} has no moves left", color_name(*winner), loser),
+                }
+            }
+            GameResult::Draw(Reason::ThreefoldRepetition) => write!(f, "Draw by threefold repetition"),
+            GameResult::Draw(_) => write!(f, "Draw, no captures or man moves for too long"),
+        }
+    }
+}
+
+// A game in progress: the board, every move played so far and the draw
+// rules. Everything that plays out a whole game goes through this so games
+// end the same way everywhere.
+#[derive(Clone, Debug)]
+pub struct Game {
+    start: checkers::Board,
+    board: checkers::Board,
+    moves: Vec<checkers::Move>,
+    // Zobrist keys of every position reached, the current one last
+    positions: Vec<u64>,
+    // plies played since the last capture or man move, at every position
+    // reached
+    quiet_plies: Vec<u32>,
+    // moves by each side without a capture or a man moving before the game
+    // is drawn, None to play on forever
+    no_progress_limit: Option<u32>,
+    result: Option<GameResult>,
+}
+
+impl Game {
+    // Starts a game from the given position, drawn after 40 moves each
+    // without a capture or a man moving.
+    pub fn new(board: checkers::Board) -> Game {
+        let mut game = Game {
+            start: board,
+            positions: vec![board.hash_key()],
+            board,
+            moves: Vec::new(),
+            quiet_plies: vec![0],
+            no_progress_limit: Some(40),
+            result: None,
+        };
+        game.result = game.find_result();
+        game
+    }
+
+    pub fn board(&self) -> &checkers::Board {
+        &self.board
+    }
+
+    pub fn start(&self) -> &checkers::Board {
+        &self.start
+    }
+
+    pub fn moves(&self) -> &[checkers::Move] {
+        &self.moves
+    }
+
+    #[allow(dead_code)]
+    pub fn no_progress_limit(&self) -> Option<u32> {
+        self.no_progress_limit
+    }
+
+    pub fn set_no_progress_limit(&mut self, moves: Option<u32>) {
+        self.no_progress_limit = moves;
+        self.result = self.find_result();
+    }
+
+    pub fn legal_moves(&self) -> Vec<checkers::Move> {
+        match self.result {
+            Some(_) => Vec::new(),
+            None => self.board.clone().legal_moves(),
+        }
+    }
+
+    pub fn parse_move(&self, text: &str) -> Result<checkers::Move, checkers::CheckersError> {
+        self.board.clone().parse_move(text)
+    }
+
+    // None while the game is still going.
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    // The result the game would have after `m`, without playing it.
+    pub fn result_after(&self, m: &checkers::Move) -> Option<GameResult> {
+        let mut game = self.clone();
+        game.make_move(m);
+        game.result
+    }
+
+    pub fn make_move(&mut self, m: &checkers::Move) {
+        self.board.push_move(m);
+        self.record_move(m);
+    }
+
+    // Like make_move, but turns away an illegal move or one that comes after
+    // the game is over.
+    pub fn try_make_move(&mut self, m: &checkers::Move) -> Result<(), checkers::CheckersError> {
+        if self.result.is_some() {
+            return Err(checkers::CheckersError::GameOver);
+        }
+
+        let legal = self.board.make_move_checked(m)?;
+        self.record_move(&legal);
+        Ok(())
+    }
+
+    // Takes back the last move, returning it, or None at the start of the
+    // game.
+    pub fn take_back(&mut self) -> Option<checkers::Move> {
+        let m = self.moves.pop()?;
+        if self.board.pop_move().is_none() {
+            // the board only remembers its last moves, so one further back
+            // is undone by playing the game again without it
+            self.board = self.start;
+            for m in self.moves.iter() {
+                self.board.push_move(m);
+            }
+        }
+
+        self.positions.pop();
+        self.quiet_plies.pop();
+        self.result = self.find_result();
+        Some(m)
+    }
+
+    // Bookkeeping once `m` has been played on the board.
+    fn record_move(&mut self, m: &checkers::Move) {
+        let to = m.jumps[m.jumps.len() - 1];
+        let man_moved = m.is_king || self.board.board[to as usize] & checkers::KING == 0;
+        let quiet_plies = match man_moved || !m.captures.is_empty() {
+            true => 0,
+            false => self.quiet_plies() + 1,
+        };
+        self.quiet_plies.push(quiet_plies);
+
+        self.moves.push(*m);
+        self.positions.push(self.board.hash_key());
+        self.result = self.find_result();
+    }
+
+    fn quiet_plies(&self) -> u32 {
+        self.quiet_plies[self.quiet_plies.len() - 1]
+    }
+
+    fn find_result(&self) -> Option<GameResult> {
+        let to_move = self.board.side_to_move();
+        if self.board.clone().legal_moves().is_empty() {
+            let winner = checkers::opponent(to_move);
+            return match self.board.board.iter().any(|piece| piece & to_move != 0) {
+                true => Some(GameResult::Win(winner, Reason::NoMovesLeft)),
+                false => Some(GameResult::Win(winner, Reason::NoPiecesLeft)),
+            };
+        }
+
+        // a capture or man move can't be undone, so only the positions since
+        // the last one can repeat
+        let current = self.board.hash_key();
+        let recent = &self.positions[self.positions.len() - 1 - self.quiet_plies() as usize..];
+        if recent.iter().filter(|&&key| key == current).count() >= 3 {
+            return Some(GameResult::Draw(Reason::ThreefoldRepetition));
+        }
+
+        match self.no_progress_limit {
+            Some(moves) if self.quiet_plies() >= moves * 2 => Some(GameResult::Draw(Reason::NoProgress)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(game: &mut Game, moves: &[&str]) {
+        for text in moves {
+            let m = game.parse_move(text).unwrap();
+            game.make_move(&m);
+        }
+    }
+
+    #[test]
+    fn threefold_repetition_draws() {
+        let mut game = Game::new(checkers::Board::from_fen("B:WK32:BK1").unwrap());
+
+        // the start position comes up a second time after 4 plies and a third
+        // after 8
+        play(&mut game, &["1-6", "32-27", "6-1", "27-32", "1-6", "32-27", "6-1"]);
+        assert_eq!(game.result(), None);
+        play(&mut game, &["27-32"]);
+        assert_eq!(game.result(), Some(GameResult::Draw(Reason::ThreefoldRepetition)));
+        assert!(game.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn no_progress_draws() {
+        let mut game = Game::new(checkers::Board::from_fen("B:WK32:BK1").unwrap());
+        game.set_no_progress_limit(Some(2));
+
+        play(&mut game, &["1-6", "32-27", "6-10"]);
+        assert_eq!(game.result(), None);
+        play(&mut game, &["27-23"]);
+        assert_eq!(game.result(), Some(GameResult::Draw(Reason::NoProgress)));
+
+        // a man moving starts the count again
+        let mut game = Game::new(checkers::Board::from_fen("B:WK32:BK1,5").unwrap());
+        game.set_no_progress_limit(Some(2));
+        play(&mut game, &["1-6", "32-27", "5-9", "27-23", "6-10"]);
+        assert_eq!(game.result(), None);
+    }
+
+    #[test]
+    fn wins_tell_no_pieces_from_no_moves() {
+        let mut game = Game::new(checkers::Board::from_fen("B:W18:B14").unwrap());
+        assert_eq!(game.result(), None);
+        play(&mut game, &["14x23"]);
+        assert_eq!(game.result(), Some(GameResult::Win(checkers::BLACK, Reason::NoPiecesLeft)));
+
+        // the white man is blocked and can't capture
+        let game = Game::new(checkers::Board::from_fen("W:W29:B25,K22").unwrap());
+        assert_eq!(game.result(), Some(GameResult::Win(checkers::BLACK, Reason::NoMovesLeft)));
+    }
+
+    #[test]
+    fn take_back_restores_the_game() {
+        let start = checkers::Board::from_fen("B:WK32:BK1").unwrap();
+        let mut game = Game::new(start);
+        play(&mut game, &["1-6", "32-27", "6-1", "27-32", "1-6", "32-27", "6-1", "27-32"]);
+        assert_eq!(game.result(), Some(GameResult::Draw(Reason::ThreefoldRepetition)));
+
+        // the repetition is undone with the move, and comes back with it
+        let m = game.take_back().unwrap();
+        assert_eq!(game.result(), None);
+        assert_eq!(game.moves().len(), 7);
+        game.make_move(&m);
+        assert_eq!(game.result(), Some(GameResult::Draw(Reason::ThreefoldRepetition)));
+
+        while game.take_back().is_some() {}
+        assert_eq!(game.board(), &start);
+        assert_eq!(game.board().hash_key(), start.hash_key());
+        assert_eq!(game.result(), None);
+
+        // taking back a win
+        let mut game = Game::new(checkers::Board::from_fen("B:W18:B14").unwrap());
+        play(&mut game, &["14x23"]);
+        game.take_back();
+        assert_eq!(game.result(), None);
+        assert_eq!(game.legal_moves().len(), 1);
+    }
+
+    #[test]
+    fn take_back_goes_further_than_the_board_remembers() {
+        let start = checkers::Board::from_fen("B:WK32:BK1").unwrap();
+        let mut game = Game::new(start);
+        game.set_no_progress_limit(None);
+
+        // make_move plays on past the repetition draw
+        let plies = checkers::MAX_HISTORY + 10;
+        for ply in 0..plies {
+            play(&mut game, &[["1-6", "32-27", "6-1", "27-32"][ply % 4]]);
+        }
+
+        for _ in 0..plies {
+            assert!(game.take_back().is_some());
+        }
+        assert_eq!(game.take_back(), None);
+        assert_eq!(game.board(), &start);
+        assert_eq!(game.result(), None);
+
+        play(&mut game, &["1-6"]);
+        assert_eq!(game.moves().len(), 1);
+    }
+}
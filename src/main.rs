@@ -1,5 +1,14 @@
 
 #[path = "checkers.rs"] mod checkers;
+#[path = "pdn.rs"] mod pdn;
+#[path = "game.rs"] mod game;
+#[cfg(test)]
+#[path = "perft.rs"] mod perft;
+#[path = "bot.rs"] mod bot;
+#[path = "ordering.rs"] mod ordering;
+#[path = "registry.rs"] mod registry;
+#[path = "search.rs"] mod search;
+#[path = "transposition.rs"] mod transposition;
 
 #[path = "bots/pick_first/checkers_bot.rs"] mod checkers_bot_v1;
 #[path = "bots/minimax/checkers_bot.rs"] mod checkers_bot_v2;
@@ -7,210 +16,411 @@
 #[path = "bots/attackdepth/checkers_bot.rs"] mod checkers_bot_v4;
 #[path = "bots/checkbestfirst/checkers_bot.rs"] mod checkers_bot_v5;
 
-fn bot_battle(mut board: checkers::Board, player_to_move: u8, bot_one: u32, bot_two: u32) -> u32 {
+fn bot_battle(board: checkers::Board, bot_one: &mut dyn bot::Bot, bot_two: &mut dyn bot::Bot) -> Result<(u32, pdn::PdnGame), checkers::CheckersError> {
     board.print();
 
-    let mut player = match player_to_move {
-        checkers::BLACK => true,
-        checkers::WHITE => false,
-        _ => panic!("Invalid player"),
-    };
-
-    let mut current_bot = bot_one;
-
-    let move_limit = 200;
-
-    let mut move_count = 0;
-
-    while move_count < move_limit {
-        let color = match player {
-            true => checkers::BLACK,
-            false => checkers::WHITE,
-        };
+    let bot_one_color = board.side_to_move();
+    let mut game = game::Game::new(board);
 
-        let (move_to_make, board_score) = match current_bot {
-            1 => checkers_bot_v1::pick_move(&mut board, color, 100),
-            2 => checkers_bot_v2::pick_move(&mut board, color, 100),
-            3 => checkers_bot_v3::pick_move(&mut board, color, 100),
-            4 => checkers_bot_v4::pick_move(&mut board, color, 100),
-            5 => checkers_bot_v5::pick_move(&mut board, color, 100),
-            _ => panic!("Invalid bot number"),
+    while game.result().is_none() {
+        let limits = bot::SearchLimits { time_ms: 100 };
+        let result = match game.board().side_to_move() == bot_one_color {
+            true => bot_one.choose_move(&game, limits)?,
+            false => bot_two.choose_move(&game, limits)?,
         };
 
-        board.make_move(&move_to_make);
-
-        player = !player;
+        game.make_move(&result.best_move);
+    }
 
-        if current_bot == bot_one {
-            current_bot = bot_two;
-        } else {
-            current_bot = bot_one;
-        }
+    game.board().print();
 
-        if board.search_black_all().len() == 0 {
-            board.print();
-            println!("White wins! {}", bot_two);
-            return 2;
+    let mut record = pdn::PdnGame::from_game(&game);
+    record.set_tag("Event", "Bot battle");
+    match bot_one_color {
+        checkers::BLACK => {
+            record.set_tag("Black", &bot_one.label());
+            record.set_tag("White", &bot_two.label());
         }
-
-        if board.search_white_all().len() == 0 {
-            board.print();
-            println!("Black wins! {}", bot_one);
-            return 1;
+        _ => {
+            record.set_tag("Black", &bot_two.label());
+            record.set_tag("White", &bot_one.label());
         }
-
-        move_count += 1;
     }
 
-    if move_count == move_limit {
-        board.print();
-        println!("Draw!");
+    let winner = match game.result() {
+        Some(game::GameResult::Win(color, _)) if color == bot_one_color => 1,
+        Some(game::GameResult::Win(_, _)) => 2,
+        _ => 0,
+    };
+
+    if let Some(result) = game.result() {
+        println!("{}", result);
     }
 
-    0
+    Ok((winner, record))
 }
 
-fn bot_battle_arena(games: u32, bot_one: u32, bot_two: u32) {
+fn bot_battle_arena(games: u32, bot_one: &mut dyn bot::Bot, bot_two: &mut dyn bot::Bot, rules: checkers::Rules, pdn_path: Option<&str>) -> Result<(), checkers::CheckersError> {
+    let mut records = String::new();
+
     let mut bot_one_wins = 0;
     let mut bot_two_wins = 0;
     let mut draws = 0;
 
     let mut index = 0;
-    let mut boards = Vec::<(checkers::Board, u8)>::new();
-    let mut board = checkers::Board::new();
-    boards.push((board.clone(), checkers::BLACK));
+    let mut boards = Vec::<checkers::Board>::new();
+    boards.push(checkers::Board::with_rules(rules));
 
     while (boards.len() as u32) < games {
-        let (mut board, color) = boards[index].clone();
-
-        let moves = match color {
-            checkers::BLACK => board.search_black_all(),
-            checkers::WHITE => board.search_white_all(),
-            _ => panic!("Invalid color"),
-        };
+        let mut board = boards[index];
 
-        for m in moves {
-            let mut new_board = board.clone();
+        for m in board.legal_moves() {
+            let mut new_board = board;
             new_board.make_move(&m);
 
-            let new_color = match color {
-                checkers::BLACK => checkers::WHITE,
-                checkers::WHITE => checkers::BLACK,
-                _ => panic!("Invalid color"),
-            };
-
-            boards.push((new_board, new_color));
+            boards.push(new_board);
         }
 
         index += 1;
     }
 
-    let mut i = 0;
-    for (b, c) in boards {
-        let other_board = b.clone();
+    for (i, b) in boards.into_iter().enumerate() {
+        let other_board = b;
 
-        let winner = bot_battle(b, c, bot_one, bot_two);
+        let (winner, game) = bot_battle(b, bot_one, bot_two)?;
+        records.push_str(&pdn::write_game(&game));
+        records.push('\n');
 
         match winner {
-            0 => draws += 1,
             1 => bot_one_wins += 1,
             2 => bot_two_wins += 1,
-            _ => panic!("Invalid winner"),
+            _ => draws += 1,
         }
 
-        let winner = bot_battle(other_board, c, bot_two, bot_one);
+        let (winner, game) = bot_battle(other_board, bot_two, bot_one)?;
+        records.push_str(&pdn::write_game(&game));
+        records.push('\n');
 
         match winner {
-            0 => draws += 1,
             1 => bot_two_wins += 1,
             2 => bot_one_wins += 1,
-            _ => panic!("Invalid winner"),
+            _ => draws += 1,
         }
 
         println!("Game {} complete", i + 1);
-        i += 1;
     }
 
     println!("Bot one wins: {}", bot_one_wins);
     println!("Bot two wins: {}", bot_two_wins);
     println!("Draws: {}", draws);
+
+    if let Some(path) = pdn_path {
+        match std::fs::write(path, records) {
+            Ok(()) => println!("Games written to {}", path),
+            Err(e) => println!("Could not write games to {}: {}", path, e),
+        }
+    }
+
+    Ok(())
 }
 
-fn main() {
-    println!("Hello, world!");
+// pdn <file> reads the games in a PDN file, checking each move against the
+// move generator as it is replayed, and lists them with their results.
+fn pdn_command(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            println!("Usage: pdn <file>");
+            return;
+        }
+    };
 
-    let mut board = checkers::Board::new();
+    let games = match std::fs::read_to_string(path) {
+        Ok(text) => pdn::parse_games(&text),
+        Err(e) => {
+            println!("Could not read {}: {}", path, e);
+            return;
+        }
+    };
+    let games = match games {
+        Ok(games) => games,
+        Err(e) => {
+            println!("{}: {}", path, e);
+            return;
+        }
+    };
 
-    let bot_battle_s = false;
+    for (i, record) in games.iter().enumerate() {
+        // the game may have been played to other draw rules, so only a
+        // repetition or a side left without moves ends it early here
+        let mut game = game::Game::new(record.start);
+        game.set_no_progress_limit(None);
+
+        let black = record.tag("Black").unwrap_or("?");
+        let white = record.tag("White").unwrap_or("?");
+        print!("Game {}: {} - {}, {} plies, {}", i + 1, black, white, record.moves.len(), record.result);
+
+        for (ply, m) in record.moves.iter().enumerate() {
+            if let Err(e) = game.try_make_move(m) {
+                print!(", stops at move {}: {}", ply / 2 + 1, e);
+                break;
+            }
+        }
+        match game.result() {
+            Some(result) => println!(" ({})", result),
+            None => println!(),
+        }
+    }
+}
 
-    // get input from user to see if bot or player goes first, true for bot, false for player
-    let mut bot = false;
-    // get input from console to see if bot goes first
-    let mut bot_input = String::new();
-    std::io::stdin().read_line(&mut bot_input).unwrap();
-    let bot_input = bot_input.trim();
-    if bot_input == "true" {
-        bot = true;
+fn rules_from_name(name: &str) -> Result<checkers::Rules, String> {
+    match name {
+        "american" => Ok(checkers::AMERICAN_RULES),
+        "majority" => Ok(checkers::MAJORITY_CAPTURE_RULES),
+        "russian" => Ok(checkers::RUSSIAN_RULES),
+        "international" => Ok(checkers::INTERNATIONAL_RULES),
+        _ => Err(format!("Unknown variant: {}, expected american, majority, russian or international", name)),
     }
+}
 
-    // get input from user to see if bot or player goes first, true for bot, false for player
-    let mut player = false;
-    let mut player_input = String::new();
-    std::io::stdin().read_line(&mut player_input).unwrap();
-    let player_input = player_input.trim();
-    if player_input == "true" {
-        player = true;
+// perft <depth> [variant] [fen] counts the positions reached from the start
+// position or the given one, divide does the same split up by first move.
+fn perft_command(command: &str, args: &[String]) {
+    let depth = match args.first().map(|depth| depth.parse::<u32>()) {
+        Some(Ok(depth)) => depth,
+        _ => {
+            println!("Usage: {} <depth> [variant] [fen]", command);
+            return;
+        }
+    };
+
+    let rules = match args.get(1).map(|name| rules_from_name(name)) {
+        Some(Ok(rules)) => rules,
+        Some(Err(e)) => {
+            println!("{}", e);
+            return;
+        }
+        None => checkers::AMERICAN_RULES,
+    };
+
+    let mut board = match args.get(2) {
+        Some(fen) => match checkers::Board::from_fen_with_rules(fen, rules) {
+            Ok(board) => board,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        },
+        None => checkers::Board::with_rules(rules),
+    };
+
+    let start_time = std::time::Instant::now();
+
+    let nodes = match command {
+        "divide" => {
+            let mut nodes = 0;
+            for (m, count) in board.divide(depth) {
+                println!("{}: {}", m, count);
+                nodes += count;
+            }
+            nodes
+        }
+        _ => board.perft(depth),
+    };
+
+    println!("Nodes: {} ({} ms)", nodes, start_time.elapsed().as_millis());
+}
+
+// bots lists the bots that can be picked by name
+fn bots_command() {
+    for entry in registry::BOTS.iter() {
+        match entry.options {
+            "" => println!("{}: {}", entry.name, entry.description),
+            options => println!("{}: {} (options: {})", entry.name, entry.description, options),
+        }
+    }
+}
+
+// battle <bot> <bot> [games] [variant] plays the two bots against each other
+// from both sides of the positions a few moves into the game, writing the
+// games to bot_battle.pdn.
+fn battle_command(args: &[String]) {
+    let (bot_one, bot_two) = match (args.first(), args.get(1)) {
+        (Some(bot_one), Some(bot_two)) => (bot_one, bot_two),
+        _ => {
+            println!("Usage: battle <bot> <bot> [games] [variant]");
+            return;
+        }
+    };
+
+    let games = match args.get(2).map(|games| games.parse::<u32>()) {
+        Some(Ok(games)) => games,
+        Some(Err(_)) => {
+            println!("Usage: battle <bot> <bot> [games] [variant]");
+            return;
+        }
+        None => 500,
+    };
+
+    let rules = match args.get(3).map(|name| rules_from_name(name)) {
+        Some(Ok(rules)) => rules,
+        Some(Err(e)) => {
+            println!("{}", e);
+            return;
+        }
+        None => checkers::AMERICAN_RULES,
+    };
+
+    let arena = registry::create(bot_one).and_then(|mut bot_one| {
+        let mut bot_two = registry::create(bot_two)?;
+        bot_battle_arena(games, bot_one.as_mut(), bot_two.as_mut(), rules, Some("bot_battle.pdn"))
+    });
+    if let Err(e) = arena {
+        println!("{}", e);
+    }
+}
+
+// A trimmed line from the console, None once there is no more input.
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+    match std::io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim().to_string()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some(command @ ("perft" | "divide")) => {
+            perft_command(command, &args[1..]);
+            return;
+        }
+        Some("pdn") => {
+            pdn_command(&args[1..]);
+            return;
+        }
+        Some("bots") => {
+            bots_command();
+            return;
+        }
+        Some("battle") => {
+            battle_command(&args[1..]);
+            return;
+        }
+        _ => (),
     }
 
-    let bot_one = 2;
-    let bot_two = 3;
+    println!("Hello, world!");
+
+    // the variant is picked by the first argument, American checkers by
+    // default, and the bot to play against by the second, e.g.
+    // alphabeta:depth=8
+    let rules = match args.first().map(|name| rules_from_name(name)) {
+        Some(Ok(rules)) => rules,
+        Some(Err(e)) => {
+            println!("{}", e);
+            return;
+        }
+        None => checkers::AMERICAN_RULES,
+    };
+
+    let mut computer = match registry::create(args.get(1).map_or("checkbestfirst", String::as_str)) {
+        Ok(computer) => computer,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    let mut board = checkers::Board::with_rules(rules);
 
-    if bot_battle_s {
-        bot_battle_arena(500, bot_one, bot_two);
-        return;
+    // get input from user to see if bot or player goes first, true for bot, false for player
+    let mut bot = read_input().as_deref() == Some("true");
+
+    // get input from user to see if black or white goes first, true for black, false for white
+    if read_input().as_deref() == Some("true") {
+        board.set_side_to_move(checkers::BLACK);
+    } else {
+        board.set_side_to_move(checkers::WHITE);
     }
 
-    while true {
-        board.print();
+    let mut game = game::Game::new(board);
+
+    while game.result().is_none() {
+        game.board().print();
 
         if bot {
-            let (move_to_make, board_score) = match player {
-                true => checkers_bot_v5::pick_move(&mut board, checkers::BLACK, 500),
-                false => checkers_bot_v5::pick_move(&mut board, checkers::WHITE, 500),
+            let result = match computer.choose_move(&game, bot::SearchLimits { time_ms: 500 }) {
+                Ok(result) => result,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
             };
 
             println!("Bot move: ");
-            move_to_make.print();
+            result.best_move.print();
 
-            println!("Board score: {}", board_score);
+            println!("Board score: {} (depth {}, {} nodes)", result.score, result.depth, result.nodes);
 
-            board.make_move(&move_to_make);
+            game.make_move(&result.best_move);
 
-            player = !player;
             bot = !bot;
             continue;
         }
 
-        let moves = match player {
-            true => board.search_black_all(),
-            false => board.search_white_all(),
-        };
+        let moves = game.legal_moves();
 
-        let mut i = 0;
-        for m in moves.iter() {
+        for (i, m) in moves.iter().enumerate() {
             print!("{}: ", i);
             m.print();
-            i += 1;
         }
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-        let index = input.parse::<usize>().unwrap();
+        let input = match read_input() {
+            Some(input) => input,
+            None => return,
+        };
+
+        // "undo" takes back the bot's last move and the player's move before
+        // it
+        if input == "undo" {
+            if game.moves().len() < 2 {
+                println!("No move to take back");
+            } else {
+                game.take_back();
+                game.take_back();
+            }
+            continue;
+        }
+
+        // a move can be picked by its number in the list or written out as
+        // e.g. 11-15 or 22x15x8
+        let move_to_make = match input.parse::<usize>() {
+            Ok(index) => match moves.get(index) {
+                Some(m) => *m,
+                None => {
+                    println!("No move number {}", index);
+                    continue;
+                }
+            },
+            Err(_) => match game.parse_move(&input) {
+                Ok(m) => m,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            },
+        };
 
-        board.make_move(&moves[index]);
+        game.make_move(&move_to_make);
 
-        player = !player;
         bot = !bot;
     }
+
+    game.board().print();
+    if let Some(result) = game.result() {
+        println!("{}", result);
+    }
 }
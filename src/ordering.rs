@@ -0,0 +1,105 @@
+use crate::checkers;
+
+// board array indexes a move can start or end on
+const SQUARES: usize = 100;
+// plies below the root that killer moves are kept for, as deep as a
+// searched line can go
+const MAX_PLY: usize = checkers::MAX_HISTORY;
+
+// Sort keys, highest searched first. Killers and history only rank quiet
+// moves: when a capture is on every move is one.
+const TABLE_MOVE: u32 = u32::MAX;
+const CAPTURE: u32 = 1 << 30;
+const KILLER: u32 = 1 << 29;
+// history scores are kept below the killers
+const MAX_HISTORY: u32 = KILLER - 1;
+
+// Decides the order a search tries moves in, so that the move most likely to
+// cause a cutoff comes first. Killer moves are quiet moves that caused a
+// cutoff at the same ply elsewhere in the tree, the history score counts the
+// cutoffs a move from and to the same squares caused anywhere.
+#[derive(Clone)]
+pub struct MoveOrdering {
+    killers: [[Option<checkers::Move>; 2]; MAX_PLY],
+    history: [[u32; SQUARES]; SQUARES],
+    // the game ply the search started from
+    root_ply: u32,
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        MoveOrdering {
+            killers: [[None; 2]; MAX_PLY],
+            history: [[0; SQUARES]; SQUARES],
+            root_ply: 0,
+        }
+    }
+
+    // Gets ready for a search from `board`. Killers belong to the ply they
+    // were found at so they are forgotten, the history is only halved so
+    // that what the last search learned still counts for something.
+    pub fn new_search(&mut self, board: &checkers::Board) {
+        self.killers = [[None; 2]; MAX_PLY];
+        for row in self.history.iter_mut() {
+            for score in row.iter_mut() {
+                *score /= 2;
+            }
+        }
+        self.root_ply = board.ply();
+    }
+
+    fn ply(&self, board: &checkers::Board) -> usize {
+        (board.ply() - self.root_ply) as usize
+    }
+
+    // Sorts the moves of the position on `board` best first: the move the
+    // transposition table has for it, then captures taking the most pieces,
+    // then killers and then quiet moves by their history score.
+    pub fn order(&self, board: &checkers::Board, moves: &mut [checkers::Move], table_move: Option<checkers::Move>) {
+        let killers = self.killers.get(self.ply(board));
+
+        moves.sort_unstable_by_key(|m| {
+            let key = if Some(*m) == table_move {
+                TABLE_MOVE
+            } else if !m.captures.is_empty() {
+                CAPTURE + m.captures.len() as u32
+            } else if killers.is_some_and(|killers| killers.contains(&Some(*m))) {
+                KILLER
+            } else {
+                self.history[m.jumps[0] as usize][m.jumps[m.jumps.len() - 1] as usize]
+            };
+            std::cmp::Reverse(key)
+        });
+    }
+
+    // Remembers that `m` caused a cutoff in a search `depth` plies deep.
+    // Captures are left out, they are searched early anyway.
+    pub fn cutoff(&mut self, board: &checkers::Board, m: &checkers::Move, depth: u32) {
+        if !m.captures.is_empty() {
+            return;
+        }
+
+        let ply = self.ply(board);
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(*m) {
+                killers[1] = killers[0];
+                killers[0] = Some(*m);
+            }
+        }
+
+        let score = &mut self.history[m.jumps[0] as usize][m.jumps[m.jumps.len() - 1] as usize];
+        *score = (*score + depth * depth).min(MAX_HISTORY);
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering::new()
+    }
+}
+
+impl std::fmt::Debug for MoveOrdering {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MoveOrdering").field("root_ply", &self.root_ply).finish()
+    }
+}
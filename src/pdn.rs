@@ -0,0 +1,410 @@
+use crate::checkers;
+use crate::game;
+
+// A game in Portable Draughts Notation. Results score the side that moves
+// first in the variant first, so in American checkers "1-0" is a black win
+// and "0-1" a white win; "1/2-1/2" is a draw and "*" an unfinished game.
+#[derive(Clone, Debug)]
+pub struct PdnGame {
+    pub tags: Vec<(String, String)>,
+    pub start: checkers::Board,
+    pub moves: Vec<checkers::Move>,
+    pub result: String,
+}
+
+impl PdnGame {
+    pub fn new(start: checkers::Board) -> PdnGame {
+        PdnGame {
+            tags: Vec::new(),
+            start,
+            moves: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+
+    // The record of a game played so far, without any tags.
+    pub fn from_game(game: &game::Game) -> PdnGame {
+        let first_to_move = game.start().rules().first_to_move;
+        let result = match game.result() {
+            Some(game::GameResult::Win(winner, _)) if winner == first_to_move => "1-0",
+            Some(game::GameResult::Win(_, _)) => "0-1",
+            Some(game::GameResult::Draw(_)) => "1/2-1/2",
+            None => "*",
+        };
+
+        PdnGame {
+            tags: Vec::new(),
+            start: *game.start(),
+            moves: game.moves().to_vec(),
+            result: String::from(result),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+}
+
+// The PDN GameType numbers of the rule sets that have one.
+fn game_type(rules: checkers::Rules) -> Option<&'static str> {
+    match rules {
+        checkers::INTERNATIONAL_RULES => Some("20"),
+        checkers::AMERICAN_RULES => Some("21"),
+        checkers::RUSSIAN_RULES => Some("25"),
+        _ => None,
+    }
+}
+
+fn rules_for_game_type(value: &str) -> Option<checkers::Rules> {
+    match value.split(',').next().map(str::trim) {
+        Some("20") => Some(checkers::INTERNATIONAL_RULES),
+        Some("21") => Some(checkers::AMERICAN_RULES),
+        Some("25") => Some(checkers::RUSSIAN_RULES),
+        _ => None,
+    }
+}
+
+// Rule sets without a GameType number are named in a Variant tag instead,
+// so they read back as what was played rather than as American checkers.
+fn variant(rules: checkers::Rules) -> Option<&'static str> {
+    match rules {
+        checkers::MAJORITY_CAPTURE_RULES => Some("majority capture"),
+        _ => None,
+    }
+}
+
+fn rules_for_variant(value: &str) -> Option<checkers::Rules> {
+    match value.trim() {
+        "majority capture" => Some(checkers::MAJORITY_CAPTURE_RULES),
+        _ => None,
+    }
+}
+
+// The position a game starts from, given by its GameType, Variant and FEN
+// tags in whichever order they come.
+fn start_position(game: &PdnGame) -> Result<checkers::Board, checkers::CheckersError> {
+    let rules = game.tag("GameType").and_then(rules_for_game_type)
+        .or_else(|| game.tag("Variant").and_then(rules_for_variant))
+        .unwrap_or(checkers::AMERICAN_RULES);
+    match game.tag("FEN") {
+        Some(fen) => checkers::Board::from_fen_with_rules(fen, rules),
+        None => Ok(checkers::Board::with_rules(rules)),
+    }
+}
+
+const RESULTS: [&str; 7] = ["1-0", "0-1", "2-0", "0-2", "1-1", "1/2-1/2", "*"];
+
+enum Token {
+    Tag(String, String),
+    Text(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, checkers::CheckersError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let mut tag = String::new();
+                let mut quoted = false;
+                loop {
+                    match chars.next() {
+                        Some('"') => {
+                            quoted = !quoted;
+                            tag.push('"');
+                        }
+                        Some(']') if !quoted => break,
+                        Some(c) => tag.push(c),
+                        None => return Err(checkers::CheckersError::InvalidPdn(String::from("unterminated tag pair"))),
+                    }
+                }
+
+                match tag.split_once('"') {
+                    Some((name, value)) => {
+                        let value = value.trim_end().trim_end_matches('"');
+                        tokens.push(Token::Tag(name.trim().to_string(), value.to_string()));
+                    }
+                    None => return Err(checkers::CheckersError::InvalidPdn(format!("invalid tag pair: [{}]", tag))),
+                }
+            }
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    return Err(checkers::CheckersError::InvalidPdn(String::from("unterminated comment")));
+                }
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => {
+                // variations are skipped, they may nest
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth -= 1,
+                        Some(_) => (),
+                        None => return Err(checkers::CheckersError::InvalidPdn(String::from("unterminated variation"))),
+                    }
+                }
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[{(;".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Text(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Reads every game in a PDN file, checking each move against the move
+// generator as the game is replayed.
+pub fn parse_games(text: &str) -> Result<Vec<PdnGame>, checkers::CheckersError> {
+    let mut games = Vec::new();
+    let mut game = PdnGame::new(checkers::Board::new());
+    let mut board = game.start;
+
+    for token in tokenize(text)? {
+        match token {
+            Token::Tag(name, value) => {
+                if !game.moves.is_empty() {
+                    games.push(game);
+                    game = PdnGame::new(checkers::Board::new());
+                }
+
+                game.set_tag(&name, &value);
+            }
+            Token::Text(word) => {
+                // the tags are all read by the time the movetext starts
+                if game.moves.is_empty() {
+                    game.start = start_position(&game)?;
+                    board = game.start;
+                }
+
+                if RESULTS.contains(&word.as_str()) {
+                    game.result = word;
+                    games.push(game);
+                    game = PdnGame::new(checkers::Board::new());
+                    continue;
+                }
+
+                // drop move numbers such as "12." or "12...", which may be
+                // written against the move, and trailing annotations
+                let word = match word.rfind('.') {
+                    Some(dot) => &word[dot + 1..],
+                    None => word.as_str(),
+                };
+                let word = word.trim_end_matches(['!', '?']);
+
+                if word.is_empty() || word.starts_with('$') {
+                    continue;
+                }
+
+                let m = board.parse_move(word)?;
+                board.push_move(&m);
+                game.moves.push(m);
+            }
+        }
+    }
+
+    if !game.moves.is_empty() || !game.tags.is_empty() {
+        if game.moves.is_empty() {
+            game.start = start_position(&game)?;
+        }
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+pub fn write_game(game: &PdnGame) -> String {
+    let mut output = String::new();
+
+    for (name, value) in game.tags.iter() {
+        if name != "Result" {
+            output.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+    }
+
+    let rules = game.start.rules();
+    if let (None, Some(value)) = (game.tag("GameType"), game_type(rules)) {
+        output.push_str(&format!("[GameType \"{}\"]\n", value));
+    }
+    if let (None, Some(value)) = (game.tag("Variant"), variant(rules)) {
+        output.push_str(&format!("[Variant \"{}\"]\n", value));
+    }
+
+    if game.tag("FEN").is_none() && game.start != checkers::Board::with_rules(rules) {
+        output.push_str(&format!("[FEN \"{}\"]\n", game.start.to_fen()));
+    }
+
+    output.push_str(&format!("[Result \"{}\"]\n", game.result));
+
+    let mut words = Vec::new();
+    let mut move_number = 1;
+    if game.start.side_to_move() != rules.first_to_move && !game.moves.is_empty() {
+        words.push(String::from("1..."));
+    }
+
+    for m in game.moves.iter() {
+        if m.color == rules.first_to_move {
+            words.push(format!("{}.", move_number));
+        } else {
+            move_number += 1;
+        }
+        words.push(m.to_string());
+    }
+    words.push(game.result.clone());
+
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.len() + word.len() >= 80 {
+            output.push_str(&line);
+            output.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    output.push_str(&line);
+    output.push('\n');
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The record of a game played with the first legal move every time, as
+    // far as `plies` or until a side has no moves left.
+    fn first_moves(start: checkers::Board, plies: usize) -> PdnGame {
+        let mut record = PdnGame::new(start);
+        let mut board = start;
+        for _ in 0..plies {
+            match board.legal_moves().first() {
+                Some(m) => {
+                    board.make_move(m);
+                    record.moves.push(*m);
+                }
+                None => break,
+            }
+        }
+        record
+    }
+
+    fn round_trip(record: &PdnGame) -> PdnGame {
+        let mut games = parse_games(&write_game(record)).expect("a written game reads back");
+        assert_eq!(games.len(), 1);
+        games.remove(0)
+    }
+
+    #[test]
+    fn game_round_trips() {
+        let mut record = first_moves(checkers::Board::new(), 60);
+        record.result = String::from("1/2-1/2");
+        record.set_tag("Event", "Bot battle");
+        record.set_tag("Black", "Bot 2");
+
+        let read = round_trip(&record);
+        assert_eq!(read.start, record.start);
+        assert_eq!(read.moves, record.moves);
+        assert_eq!(read.result, record.result);
+        assert_eq!(read.tag("Event"), Some("Bot battle"));
+        assert_eq!(read.tag("Black"), Some("Bot 2"));
+    }
+
+    #[test]
+    fn position_round_trips() {
+        // white to move although black starts, so the move text starts
+        // with "1..."
+        let start = checkers::Board::from_fen("W:W14,18,23,27,K32,K9:B5,10,12,K20,K26,21").unwrap();
+        let record = first_moves(start, 12);
+
+        let read = round_trip(&record);
+        assert_eq!(read.start, record.start);
+        assert_eq!(read.moves, record.moves);
+        assert_eq!(read.result, "*");
+    }
+
+    #[test]
+    fn variants_round_trip() {
+        for rules in [checkers::RUSSIAN_RULES, checkers::INTERNATIONAL_RULES, checkers::MAJORITY_CAPTURE_RULES] {
+            let record = first_moves(checkers::Board::with_rules(rules), 40);
+
+            let read = round_trip(&record);
+            assert_eq!(read.start.rules(), rules);
+            assert_eq!(read.start, record.start);
+            assert_eq!(read.moves, record.moves);
+        }
+
+        // a position keeps its rules as well
+        let mut start = checkers::Board::from_fen("W:W14,18,23,27,K32,K9:B5,10,12,K20,K26,21").unwrap();
+        start.set_rules(checkers::MAJORITY_CAPTURE_RULES);
+        let read = round_trip(&first_moves(start, 12));
+        assert_eq!(read.start, start);
+
+        let start = checkers::Board::from_fen_with_rules("W:W7,8,K28,33,39,44,K46:B12,13,19,K23,24,29,K35,40", checkers::INTERNATIONAL_RULES).unwrap();
+        let record = first_moves(start, 12);
+        let read = round_trip(&record);
+        assert_eq!(read.start, record.start);
+        assert_eq!(read.moves, record.moves);
+    }
+
+    #[test]
+    fn reads_annotated_games() {
+        let text = r#"
+            [Event "Club game"]
+            [Black "A"]
+            [White "B"]
+            1. 11-15 {a comment} 22-18 2. 15x22 25x18!? (2... 18x11 3. 8x15) $1
+            3. 8-11 29-25 4.4-8 25-22 5. 12-16 24-20 ; to the end of the line
+            6. 10-15 27-24 7. 16-19 23x16
+            1/2-1/2
+
+            [Event "Second"]
+            1. 12-16 24-20 *
+        "#;
+
+        let games = parse_games(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("Event"), Some("Club game"));
+        assert_eq!(games[0].moves.len(), 14);
+        assert_eq!(games[0].moves[3].to_string(), "25x18");
+        assert_eq!(games[0].moves[13].to_string(), "23x16");
+        assert_eq!(games[0].result, "1/2-1/2");
+
+        assert_eq!(games[1].tag("Event"), Some("Second"));
+        assert_eq!(games[1].moves.len(), 2);
+        assert_eq!(games[1].result, "*");
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        assert!(parse_games("1. 11-15 22-18 2. 15-19 *").is_err());
+        assert!(parse_games("[Event \"unfinished").is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use crate::checkers;
+use std::collections::HashSet;
+
+// A position and its perft counts, from depth 1 up.
+struct PerftCase {
+    name: &'static str,
+    rules: checkers::Rules,
+    // None for the start position of the variant
+    fen: Option<&'static str>,
+    counts: &'static [u64],
+}
+
+// The start position and Woldouby counts are the published ones. The other
+// positions put kings and crowning captures in play; their counts come from
+// the brute force generator below, which all the counts are checked
+// against.
+const SUITE: [PerftCase; 11] = [
+    PerftCase {
+        name: "American start",
+        rules: checkers::AMERICAN_RULES,
+        fen: None,
+        counts: &[7, 49, 302, 1469, 7361, 36768, 179740, 845931, 3963680],
+    },
+    PerftCase {
+        name: "American kings",
+        rules: checkers::AMERICAN_RULES,
+        fen: Some("B:W14,18,23,27,K32,K9:B5,10,12,K20,K26,21"),
+        counts: &[2, 9, 13, 60, 271, 1224, 6645, 33542, 195742],
+    },
+    PerftCase {
+        name: "American crowning",
+        rules: checkers::AMERICAN_RULES,
+        fen: Some("W:W6,7,K19:B2,3,10,11,K27,K28,16"),
+        counts: &[1, 1, 3, 16, 52, 367, 763, 7250, 19930],
+    },
+    PerftCase {
+        name: "Majority capture kings",
+        rules: checkers::MAJORITY_CAPTURE_RULES,
+        fen: Some("B:W14,18,23,27,K32,K9:B5,10,12,K20,K26,21"),
+        counts: &[2, 9, 10, 46, 239, 1100, 5863, 30017],
+    },
+    PerftCase {
+        name: "Russian start",
+        rules: checkers::RUSSIAN_RULES,
+        fen: None,
+        counts: &[7, 49, 302, 1469, 7482, 37986, 190146, 929899],
+    },
+    PerftCase {
+        name: "Russian kings",
+        rules: checkers::RUSSIAN_RULES,
+        fen: Some("W:W14,18,23,27,K32,K9:B5,10,12,K20,K26,21"),
+        counts: &[2, 6, 21, 62, 368, 2351, 14795, 113219],
+    },
+    PerftCase {
+        name: "Russian crowning",
+        rules: checkers::RUSSIAN_RULES,
+        fen: Some("W:W6,7,K19:B2,3,10,11,K27,K28,16"),
+        counts: &[3, 14, 28, 233, 1076, 9664, 53869],
+    },
+    PerftCase {
+        name: "International start",
+        rules: checkers::INTERNATIONAL_RULES,
+        fen: None,
+        counts: &[9, 81, 658, 4265, 27117, 167140, 1049442, 6483961],
+    },
+    PerftCase {
+        name: "International Woldouby",
+        rules: checkers::INTERNATIONAL_RULES,
+        fen: Some("W:W25,27,28,30,32,33,34,35,37,38:B12,13,14,16,18,19,21,23,24,26"),
+        counts: &[6, 12, 30, 73, 215, 590, 1944, 6269, 22369],
+    },
+    PerftCase {
+        name: "International kings",
+        rules: checkers::INTERNATIONAL_RULES,
+        fen: Some("W:W7,8,K28,33,39,44,K46:B12,13,19,K23,24,29,K35,40"),
+        counts: &[1, 5, 35, 82, 800, 5188],
+    },
+    // hundreds of capture sequences, all taking every black piece, that come
+    // to 19 distinct moves
+    PerftCase {
+        name: "International sweep",
+        rules: checkers::INTERNATIONAL_RULES,
+        fen: Some("W:WK1,K5,K45,K49:B7,8,K9,10,K20,21,22,K23,30,31,32,K39,40,41,K42"),
+        counts: &[19, 0],
+    },
+];
+
+// How far the suite is run on every test run, the full counts take too
+// long for an unoptimized build. The ignored tests run all of them:
+// cargo test --release -- --ignored
+const QUICK_DEPTH: u32 = 5;
+const QUICK_BRUTE_FORCE_NODES: u64 = 100_000;
+
+// One board for every position in the suite, for the search tests.
+pub fn suite_boards() -> Vec<checkers::Board> {
+    SUITE.iter().map(suite_board).collect()
+}
+
+fn suite_board(case: &PerftCase) -> checkers::Board {
+    match case.fen {
+        Some(fen) => checkers::Board::from_fen_with_rules(fen, case.rules).expect("suite FEN is valid"),
+        None => checkers::Board::with_rules(case.rules),
+    }
+}
+
+// Runs every position of the suite up to `max_depth`, and each position
+// flipped, which has to give the same counts.
+fn check_suite(max_depth: u32) {
+    let mut failures = Vec::new();
+
+    for case in SUITE.iter() {
+        let mut board = suite_board(case);
+        let mut flipped = board.flipped();
+
+        for (depth, &expected) in (1..=max_depth).zip(case.counts.iter()) {
+            let nodes = board.perft(depth);
+            if nodes != expected {
+                failures.push(format!("{} depth {}: expected {}, got {}", case.name, depth, expected, nodes));
+            }
+
+            let nodes = flipped.perft(depth);
+            if nodes != expected {
+                failures.push(format!("{} flipped, depth {}: expected {}, got {}", case.name, depth, expected, nodes));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn quick_suite() {
+    check_suite(QUICK_DEPTH);
+}
+
+#[test]
+#[ignore]
+fn full_suite() {
+    check_suite(u32::MAX);
+}
+
+// Board::moves has to hand out the same moves as generate_moves, each of
+// them once, in every position reached within `depth` plies.
+fn check_lazy_moves(board: &mut checkers::Board, depth: u32) {
+    let mut generated = checkers::MoveList::new();
+    board.generate_moves(&mut generated);
+
+    let mut captures = checkers::MoveList::new();
+    let lazy: Vec<checkers::Move> = board.moves(&mut captures).collect();
+
+    let generated_set: HashSet<checkers::Move> = generated.iter().copied().collect();
+    let lazy_set: HashSet<checkers::Move> = lazy.iter().copied().collect();
+    assert_eq!(lazy.len(), generated.len(), "{}", board.to_fen());
+    assert!(lazy_set == generated_set, "{}", board.to_fen());
+
+    if depth > 1 {
+        for m in generated.iter() {
+            board.make_move(m);
+            check_lazy_moves(board, depth - 1);
+            board.undo_move(m);
+        }
+    }
+}
+
+#[test]
+fn lazy_moves_match_generated_moves() {
+    for case in SUITE.iter() {
+        check_lazy_moves(&mut suite_board(case), QUICK_DEPTH - 1);
+    }
+}
+
+// Checks the suite counts up to `max_nodes` against the brute force
+// generator.
+fn check_brute_force(max_nodes: u64) {
+    let mut failures = Vec::new();
+
+    for case in SUITE.iter() {
+        let position = brute_force::Position::new(&suite_board(case));
+
+        for (depth, &expected) in (1..).zip(case.counts.iter()).take_while(|(_, &expected)| expected <= max_nodes) {
+            let nodes = position.perft(depth);
+            if nodes != expected {
+                failures.push(format!("{} depth {}: expected {}, brute force got {}", case.name, depth, expected, nodes));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn quick_brute_force() {
+    check_brute_force(QUICK_BRUTE_FORCE_NODES);
+}
+
+#[test]
+#[ignore]
+fn full_brute_force() {
+    check_brute_force(u64::MAX);
+}
+
+// A second move generator, as plain as it can be and sharing nothing with
+// the one in checkers.rs but the rules, to check the suite counts against.
+// It walks a grid of rows and columns a step at a time and writes out
+// every capture sequence, where the board keeps bitboards and works out
+// what it can ahead of time.
+mod brute_force {
+    use crate::checkers;
+
+    type Square = (usize, usize);
+
+    const DIRECTIONS: [(isize, isize); 4] = [(1, -1), (1, 1), (-1, -1), (-1, 1)];
+
+    #[derive(Clone)]
+    pub struct Position {
+        rules: checkers::Rules,
+        size: usize,
+        // the piece on each square by row and column, row 0 holding squares
+        // 1 and up, black's back row
+        grid: [[u8; 10]; 10],
+        turn: u8,
+    }
+
+    // A finished capture sequence.
+    struct Capture {
+        to: Square,
+        captured: Vec<Square>,
+        piece: u8,
+    }
+
+    impl Position {
+        pub fn new(board: &checkers::Board) -> Position {
+            let rules = board.rules();
+            let size = rules.board_size as usize;
+            let mut position = Position { rules, size, grid: [[checkers::EMPTY; 10]; 10], turn: board.side_to_move() };
+
+            // the pieces are read back from the FEN, which numbers the
+            // squares the standard way
+            let row_squares = size / 2;
+            for part in board.to_fen().split(':').skip(1) {
+                let color = match part.starts_with('W') {
+                    true => checkers::WHITE,
+                    false => checkers::BLACK,
+                };
+                for text in part[1..].split(',').filter(|text| !text.is_empty()) {
+                    let (piece, number) = match text.strip_prefix('K') {
+                        Some(number) => (color | checkers::KING, number),
+                        None => (color, text),
+                    };
+                    let number: usize = number.parse().expect("a square number");
+                    let row = (number - 1) / row_squares;
+                    let column = (number - 1) % row_squares * 2 + (1 - row % 2);
+                    position.grid[row][column] = piece;
+                }
+            }
+
+            position
+        }
+
+        pub fn perft(&self, depth: u32) -> u64 {
+            let children = self.children();
+            match depth {
+                1 => children.len() as u64,
+                _ => children.iter().map(|child| child.perft(depth - 1)).sum(),
+            }
+        }
+
+        fn step(&self, (row, column): Square, (rows, columns): (isize, isize)) -> Option<Square> {
+            let row = row.checked_add_signed(rows).filter(|row| *row < self.size)?;
+            let column = column.checked_add_signed(columns).filter(|column| *column < self.size)?;
+            Some((row, column))
+        }
+
+        fn piece(&self, (row, column): Square) -> u8 {
+            self.grid[row][column]
+        }
+
+        fn forward(&self, color: u8) -> isize {
+            match color {
+                checkers::BLACK => 1,
+                _ => -1,
+            }
+        }
+
+        fn promotes(&self, color: u8, (row, _): Square) -> bool {
+            match color {
+                checkers::BLACK => row == self.size - 1,
+                _ => row == 0,
+            }
+        }
+
+        // The positions after every legal move, a move counted once however
+        // many capture sequences come to it.
+        fn children(&self) -> Vec<Position> {
+            let mut captures = Vec::new();
+            let mut quiet = Vec::new();
+
+            for row in 0..self.size {
+                for column in 0..self.size {
+                    let from = (row, column);
+                    let piece = self.piece(from);
+                    if piece & self.turn == 0 {
+                        continue;
+                    }
+
+                    self.captures(from, from, piece, &mut Vec::new(), &mut captures);
+                    self.quiet_moves(from, piece, &mut quiet);
+                }
+            }
+
+            if captures.is_empty() {
+                return quiet;
+            }
+
+            let most = captures.iter().map(|(_, capture)| capture.captured.len()).max().unwrap_or(0);
+            let mut seen = std::collections::BTreeSet::new();
+            let mut children = Vec::new();
+            for (from, mut capture) in captures {
+                if self.rules.majority_capture && capture.captured.len() < most {
+                    continue;
+                }
+                capture.captured.sort();
+                if seen.insert((from, capture.to, capture.captured.clone(), capture.piece)) {
+                    children.push(self.play(from, capture.to, &capture.captured, capture.piece));
+                }
+            }
+
+            children
+        }
+
+        fn quiet_moves(&self, from: Square, piece: u8, children: &mut Vec<Position>) {
+            let king = piece & checkers::KING != 0;
+
+            for direction in DIRECTIONS {
+                if !king && direction.0 != self.forward(piece) {
+                    continue;
+                }
+
+                let mut to = from;
+                while let Some(next) = self.step(to, direction).filter(|next| self.piece(*next) == checkers::EMPTY) {
+                    to = next;
+                    let crowned = match !king && self.promotes(piece, to) {
+                        true => piece | checkers::KING,
+                        false => piece,
+                    };
+                    children.push(self.play(from, to, &[], crowned));
+
+                    if !(king && self.rules.flying_kings) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Whether a square holds something a capture can't pass: any piece
+        // but the one capturing, which has left `from`. Captured pieces stay
+        // until the move is over.
+        fn blocked(&self, square: Square, from: Square) -> bool {
+            square != from && self.piece(square) != checkers::EMPTY
+        }
+
+        // The captures open to `piece` standing on `at`: the piece taken and
+        // the squares it can land on.
+        fn jumps(&self, from: Square, at: Square, piece: u8, captured: &[Square]) -> Vec<(Square, Vec<Square>)> {
+            let king = piece & checkers::KING != 0;
+            let flying = king && self.rules.flying_kings;
+            let mut jumps = Vec::new();
+
+            for direction in DIRECTIONS {
+                if !king && !self.rules.men_capture_backwards && direction.0 != self.forward(piece) {
+                    continue;
+                }
+
+                let mut over = self.step(at, direction);
+                while flying && over.is_some_and(|square| !self.blocked(square, from)) {
+                    over = over.and_then(|square| self.step(square, direction));
+                }
+
+                let Some(over) = over else { continue };
+                let opponent = self.piece(over) != checkers::EMPTY && self.piece(over) & piece & (checkers::WHITE | checkers::BLACK) == 0;
+                if over == from || !opponent || captured.contains(&over) {
+                    continue;
+                }
+
+                let mut landings = Vec::new();
+                let mut landing = self.step(over, direction);
+                while let Some(square) = landing.filter(|square| !self.blocked(*square, from)) {
+                    landings.push(square);
+                    if !flying {
+                        break;
+                    }
+                    landing = self.step(square, direction);
+                }
+
+                if !landings.is_empty() {
+                    jumps.push((over, landings));
+                }
+            }
+
+            jumps
+        }
+
+        // Writes out every capture sequence going on from `at`, where the
+        // piece that started on `from` has taken `captured` so far.
+        fn captures(&self, from: Square, at: Square, piece: u8, captured: &mut Vec<Square>, found: &mut Vec<(Square, Capture)>) {
+            let jumps = self.jumps(from, at, piece, captured);
+
+            if jumps.is_empty() {
+                if !captured.is_empty() {
+                    let piece = match self.promotes(piece, at) {
+                        true => piece | checkers::KING,
+                        false => piece,
+                    };
+                    found.push((from, Capture { to: at, captured: captured.clone(), piece }));
+                }
+                return;
+            }
+
+            for (over, mut landings) in jumps {
+                captured.push(over);
+
+                // a king has to land where it can carry on capturing, if it
+                // can anywhere
+                let continuing: Vec<Square> = landings.iter().copied().filter(|landing| !self.jumps(from, *landing, piece, captured).is_empty()).collect();
+                if landings.len() > 1 && !continuing.is_empty() {
+                    landings = continuing;
+                }
+
+                for landing in landings {
+                    let crowned = piece & checkers::KING == 0 && self.promotes(piece, landing);
+                    match (crowned, self.rules.crowning) {
+                        (true, checkers::Crowning::EndsMove) => {
+                            found.push((from, Capture { to: landing, captured: captured.clone(), piece: piece | checkers::KING }));
+                        }
+                        (true, checkers::Crowning::ContinuesAsKing) => self.captures(from, landing, piece | checkers::KING, captured, found),
+                        _ => self.captures(from, landing, piece, captured, found),
+                    }
+                }
+
+                captured.pop();
+            }
+        }
+
+        fn play(&self, from: Square, to: Square, captured: &[Square], piece: u8) -> Position {
+            let mut child = self.clone();
+            child.grid[from.0][from.1] = checkers::EMPTY;
+            for square in captured {
+                child.grid[square.0][square.1] = checkers::EMPTY;
+            }
+            child.grid[to.0][to.1] = piece;
+            child.turn = match self.turn {
+                checkers::BLACK => checkers::WHITE,
+                _ => checkers::BLACK,
+            };
+            child
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::bot;
+use crate::checkers;
+use crate::checkers_bot_v1;
+use crate::checkers_bot_v2;
+use crate::checkers_bot_v3;
+use crate::checkers_bot_v4;
+use crate::checkers_bot_v5;
+
+// A bot that can be asked for by name.
+pub struct Entry {
+    pub name: &'static str,
+    pub description: &'static str,
+    // the options it takes, comma separated
+    pub options: &'static str,
+    create: fn() -> Box<dyn bot::Bot>,
+}
+
+pub const BOTS: [Entry; 5] = [
+    Entry {
+        name: "pick_first",
+        description: "plays the first legal move",
+        options: "",
+        create: || Box::new(checkers_bot_v1::PickFirst),
+    },
+    Entry {
+        name: "minimax",
+        description: "plain minimax, deepening while time lasts",
+        options: "depth",
+        create: || Box::new(checkers_bot_v2::Minimax::new()),
+    },
+    Entry {
+        name: "alphabeta",
+        description: "minimax with alpha-beta pruning",
+        options: "depth,hash",
+        create: || Box::new(checkers_bot_v3::AlphaBeta::new()),
+    },
+    Entry {
+        name: "attackdepth",
+        description: "alpha-beta that plays out captures before evaluating",
+        options: "depth,hash",
+        create: || Box::new(checkers_bot_v4::AttackDepth::new()),
+    },
+    Entry {
+        name: "checkbestfirst",
+        description: "the same search as attackdepth",
+        options: "depth,hash",
+        create: || Box::new(checkers_bot_v5::CheckBestFirst::new()),
+    },
+];
+
+// Makes a bot from its name, optionally followed by a colon and its options
+// as name=value pairs separated by commas, e.g. "alphabeta:depth=8".
+pub fn create(spec: &str) -> Result<Box<dyn bot::Bot>, checkers::CheckersError> {
+    let (name, options) = match spec.split_once(':') {
+        Some((name, options)) => (name, Some(options)),
+        None => (spec, None),
+    };
+
+    let entry = match BOTS.iter().find(|entry| entry.name == name) {
+        Some(entry) => entry,
+        None => return Err(checkers::CheckersError::UnknownBot(name.to_string())),
+    };
+
+    let mut bot = (entry.create)();
+    for option in options.into_iter().flat_map(|options| options.split(',')) {
+        match option.split_once('=') {
+            Some((option, value)) => bot.set_option(option.trim(), value.trim())?,
+            None => return Err(checkers::CheckersError::InvalidBotOption(format!("expected name=value, got {}", option))),
+        }
+    }
+
+    Ok(bot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_option_is_bounded() {
+        for name in ["alphabeta", "attackdepth", "checkbestfirst"] {
+            assert!(create(&format!("{}:hash=1", name)).is_ok());
+
+            for value in ["0", "1025", "-1", "lots"] {
+                match create(&format!("{}:hash={}", name, value)) {
+                    Err(checkers::CheckersError::InvalidBotOption(_)) => (),
+                    _ => panic!("{} took hash={}", name, value),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn alphabeta_scores_like_minimax() {
+        let limits = bot::SearchLimits { time_ms: 60_000 };
+
+        for board in crate::perft::suite_boards() {
+            let game = crate::game::Game::new(board);
+            for depth in 1..=5 {
+                let minimax = create(&format!("minimax:depth={}", depth)).unwrap().choose_move(&game, limits).unwrap();
+                let alphabeta = create(&format!("alphabeta:depth={}", depth)).unwrap().choose_move(&game, limits).unwrap();
+                assert_eq!(alphabeta.score, minimax.score, "{} at depth {}", board.to_fen(), depth);
+            }
+        }
+    }
+
+    #[test]
+    fn alphabeta_searches_fewer_nodes_than_minimax() {
+        let game = crate::game::Game::new(checkers::Board::new());
+        let limits = bot::SearchLimits { time_ms: 60_000 };
+
+        let minimax = create("minimax:depth=5").unwrap().choose_move(&game, limits).unwrap();
+        let alphabeta = create("alphabeta:depth=5").unwrap().choose_move(&game, limits).unwrap();
+        assert_eq!((minimax.depth, alphabeta.depth), (5, 5));
+
+        // with integer scores a tie with the window cuts off too, so the
+        // pruning and ordering leave a small part of the tree
+        assert!(alphabeta.nodes * 5 < minimax.nodes, "alphabeta {} nodes, minimax {}", alphabeta.nodes, minimax.nodes);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::bot;
+use crate::checkers;
+use crate::game;
+use crate::ordering;
+use crate::transposition;
+
+// Scores are in hundredths of a man, from the point of view of the side to
+// move.
+pub type Score = i32;
+
+pub const MAN_VALUE: Score = 100;
+pub const KING_VALUE: Score = 300;
+// The score of a won position, less the plies it takes to win so that a
+// quicker win scores higher. Anything within MAX_PLY of it is a win.
+pub const WIN: Score = 1_000_000;
+// the deepest a search goes below the root, the captures played out at the
+// end included, no deeper than the board remembers so that pop_move can take
+// back every move of a line
+pub const MAX_PLY: u32 = checkers::MAX_HISTORY as u32;
+// beyond any score a position can have
+const INFINITY: Score = WIN + 1;
+// how far either side of the last depth's score the next depth first looks,
+// doubled each time the score falls outside
+const ASPIRATION_WINDOW: Score = MAN_VALUE / 2;
+
+// Material, from the point of view of the side to move.
+pub fn evaluate(board: &checkers::Board) -> Score {
+    let black = board.piece_count(checkers::BLACK) as Score * MAN_VALUE + board.piece_count(checkers::BLACK_KING) as Score * KING_VALUE;
+    let white = board.piece_count(checkers::WHITE) as Score * MAN_VALUE + board.piece_count(checkers::WHITE_KING) as Score * KING_VALUE;
+
+    match board.side_to_move() {
+        checkers::BLACK => black - white,
+        _ => white - black,
+    }
+}
+
+fn is_win(score: Score) -> bool {
+    score.abs() >= WIN - MAX_PLY as Score
+}
+
+// Wins are stored counting the plies from the position rather than from the
+// root, so they stay right when the position is reached at another ply.
+fn score_to_table(score: Score, ply: u32) -> Score {
+    match is_win(score) {
+        true => score + score.signum() * ply as Score,
+        false => score,
+    }
+}
+
+fn score_from_table(score: Score, ply: u32) -> Score {
+    match is_win(score) {
+        true => score - score.signum() * ply as Score,
+        false => score,
+    }
+}
+
+// What sets the search bots apart.
+#[derive(Clone, Copy, Debug)]
+pub struct Features {
+    // alpha-beta pruning, helped by a transposition table and move ordering,
+    // rather than looking at every move
+    pub pruning: bool,
+    // playing out the captures left at the end of the search before
+    // evaluating, see Search::quiescence
+    pub quiescence: bool,
+}
+
+// A negamax search, deepened a ply at a time for as long as time allows,
+// shared by the search bots.
+#[derive(Clone, Debug)]
+pub struct Search {
+    pub features: Features,
+    // the deepest it searches, None to go as deep as time allows
+    pub max_depth: Option<u32>,
+    // None when there is no pruning, as a table only pays for itself then
+    table: Option<transposition::TranspositionTable>,
+    ordering: ordering::MoveOrdering,
+    // the game ply the search started from
+    root_ply: u32,
+}
+
+impl Search {
+    pub fn new(features: Features) -> Search {
+        Search {
+            features,
+            max_depth: None,
+            table: features.pruning.then(transposition::TranspositionTable::default),
+            ordering: ordering::MoveOrdering::new(),
+            root_ply: 0,
+        }
+    }
+
+    // The settings away from their defaults, for Bot::configuration.
+    pub fn configuration(&self) -> String {
+        let mut options = Vec::new();
+        if let Some(depth) = self.max_depth {
+            options.push(format!("depth={}", depth));
+        }
+        if let Some(table) = self.table.as_ref().filter(|table| table.size_mb() != transposition::DEFAULT_SIZE_MB) {
+            options.push(format!("hash={}", table.size_mb()));
+        }
+        options.join(",")
+    }
+
+    // Sets depth, the most plies to search, or hash, the megabytes for the
+    // transposition table, for Bot::set_option of the bot called `bot`.
+    pub fn set_option(&mut self, bot: &str, name: &str, value: &str) -> Result<(), checkers::CheckersError> {
+        match name {
+            "depth" => match value.parse::<u32>() {
+                Ok(depth) if depth > 0 => {
+                    self.max_depth = Some(depth);
+                    Ok(())
+                }
+                _ => Err(checkers::CheckersError::InvalidBotOption(format!("depth must be a whole number of plies, got {}", value))),
+            },
+            "hash" if self.table.is_some() => match value.parse::<usize>() {
+                Ok(size_mb) if (1..=transposition::MAX_SIZE_MB).contains(&size_mb) => {
+                    self.table = Some(transposition::TranspositionTable::new(size_mb));
+                    Ok(())
+                }
+                _ => Err(checkers::CheckersError::InvalidBotOption(format!("hash must be a whole number of megabytes from 1 to {}, got {}", transposition::MAX_SIZE_MB, value))),
+            },
+            _ => Err(checkers::CheckersError::InvalidBotOption(format!("{} has no option {}", bot, name))),
+        }
+    }
+
+    // Searches a ply deeper each time round until the time runs out, playing
+    // the best move of the deepest search that finished.
+    pub fn choose_move(&mut self, game: &game::Game, limits: bot::SearchLimits) -> Result<bot::SearchResult, checkers::CheckersError> {
+        let mut board = *game.board();
+
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            return Err(checkers::CheckersError::NoLegalMoves);
+        }
+
+        let mut deadline = bot::Deadline::new(limits);
+        if let Some(table) = self.table.as_mut() {
+            table.set_rules(board.rules());
+            table.new_search();
+        }
+        self.ordering.new_search(&board);
+        self.root_ply = board.ply();
+
+        // what it plays if not even a one ply search finishes in time
+        let mut best_move = moves[0];
+        let mut score = evaluate(&board);
+        let mut depth = 0;
+
+        while depth < MAX_PLY && !deadline.out_of_time() && self.max_depth.is_none_or(|max_depth| depth < max_depth) {
+            // the first depth has no score to aim at
+            let result = match self.features.pruning && depth > 0 {
+                true => self.aspiration_search(game, &mut board, depth + 1, score, &mut deadline),
+                false => self.search_root(game, &mut board, depth + 1, -INFINITY, INFINITY, &mut deadline),
+            };
+
+            match result {
+                Some((m, s)) => (best_move, score) = (m, s),
+                None => break,
+            }
+
+            depth += 1;
+
+            // searching deeper can't change a forced win or loss, or the
+            // move when there is only one
+            if is_win(score) || moves.len() == 1 {
+                break;
+            }
+        }
+
+        // the bots report scores for black, in men
+        let score = match board.side_to_move() {
+            checkers::BLACK => score,
+            _ => -score,
+        };
+
+        Ok(bot::SearchResult {
+            best_move,
+            score: score as f64 / MAN_VALUE as f64,
+            depth,
+            nodes: deadline.nodes(),
+        })
+    }
+
+    fn ply(&self, board: &checkers::Board) -> u32 {
+        board.ply() - self.root_ply
+    }
+
+    // Scores the move just made on `board` for the side that made it. The
+    // first move searched is expected to be the best, so with pruning the
+    // rest are only shown to be no better with a null window around alpha,
+    // which is much cheaper, and searched again in full only when that
+    // fails.
+    fn search_move(&mut self, board: &mut checkers::Board, depth: u32, first: bool, alpha: Score, beta: Score, deadline: &mut bot::Deadline) -> Score {
+        if !self.features.pruning {
+            return -self.negamax(board, depth, -INFINITY, INFINITY, deadline);
+        }
+
+        if first {
+            return -self.negamax(board, depth, -beta, -alpha, deadline);
+        }
+
+        let score = -self.negamax(board, depth, -alpha - 1, -alpha, deadline);
+        match score > alpha && score < beta {
+            true => -self.negamax(board, depth, -beta, -alpha, deadline),
+            false => score,
+        }
+    }
+
+    // Searches the root with a narrow window around `guess`, the score of
+    // the depth before, as the score seldom moves far from one depth to the
+    // next. When the score falls outside the window it is widened on that
+    // side and the root searched again.
+    fn aspiration_search(&mut self, game: &game::Game, board: &mut checkers::Board, depth: u32, guess: Score, deadline: &mut bot::Deadline) -> Option<(checkers::Move, Score)> {
+        let mut window = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = (guess - window, guess + window);
+
+        loop {
+            let (best_move, score) = self.search_root(game, board, depth, alpha, beta, deadline)?;
+
+            if score <= alpha {
+                alpha = (score - window).max(-INFINITY);
+            } else if score >= beta {
+                beta = (score + window).min(INFINITY);
+            } else {
+                return Some((best_move, score));
+            }
+
+            window *= 2;
+        }
+    }
+
+    // The best move and its score, None when the time ran out before every
+    // move was searched. Like negamax the score is only exact when it falls
+    // inside the window alpha to beta. Unlike the rest of the tree the root
+    // knows the game, so a move the draw rules end the game on is worth
+    // nothing to either side.
+    fn search_root(&mut self, game: &game::Game, board: &mut checkers::Board, depth: u32, mut alpha: Score, beta: Score, deadline: &mut bot::Deadline) -> Option<(checkers::Move, Score)> {
+        let key = board.hash_key();
+        let table_move = self.table.as_ref().and_then(|table| table.probe(key)).and_then(|entry| entry.best_move);
+
+        let mut moves = checkers::MoveList::new();
+        board.generate_moves(&mut moves);
+        if self.features.pruning {
+            self.ordering.order(board, &mut moves, table_move);
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = moves[0];
+
+        for (index, m) in moves.iter().enumerate() {
+            board.push_move(m);
+
+            let score = match game.result_after(m) {
+                Some(game::GameResult::Draw(_)) => 0,
+                _ => self.search_move(board, depth - 1, index == 0, alpha, beta, deadline),
+            };
+
+            board.pop_move().expect("the line searched fits in the history");
+
+            if deadline.stopped() {
+                return None;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = *m;
+            }
+
+            if self.features.pruning && score >= beta {
+                break;
+            }
+
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        if let Some(table) = self.table.as_mut() {
+            table.store(key, depth, transposition::bound(best_score, original_alpha, beta), best_score, Some(best_move));
+        }
+
+        Some((best_move, best_score))
+    }
+
+    // Fail-soft alpha-beta: the score returned may lie outside the window,
+    // when it is at most alpha it is an upper bound on the position's value
+    // and when it is at least beta a lower bound.
+    fn negamax(&mut self, board: &mut checkers::Board, depth: u32, mut alpha: Score, beta: Score, deadline: &mut bot::Deadline) -> Score {
+        if deadline.poll() {
+            return 0;
+        }
+
+        let ply = self.ply(board);
+        if ply >= MAX_PLY {
+            return evaluate(board);
+        }
+
+        if depth == 0 {
+            return match self.features.quiescence {
+                true => self.quiescence(board, alpha, beta, deadline),
+                false => evaluate(board),
+            };
+        }
+
+        let key = board.hash_key();
+        let mut table_move = None;
+        if let Some(entry) = self.table.as_ref().and_then(|table| table.probe(key)) {
+            let score = score_from_table(entry.score, ply);
+            if entry.depth >= depth && entry.bound.settles(score, alpha, beta) {
+                return score;
+            }
+            table_move = entry.best_move;
+        }
+
+        let mut moves = checkers::MoveList::new();
+        board.generate_moves(&mut moves);
+
+        // the side to move has lost
+        if moves.is_empty() {
+            return -(WIN - ply as Score);
+        }
+
+        if self.features.pruning {
+            self.ordering.order(board, &mut moves, table_move);
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = moves[0];
+
+        for (index, m) in moves.iter().enumerate() {
+            board.push_move(m);
+
+            let score = self.search_move(board, depth - 1, index == 0, alpha, beta, deadline);
+
+            board.pop_move().expect("the line searched fits in the history");
+
+            if score > best_score {
+                best_score = score;
+                best_move = *m;
+            }
+
+            if !self.features.pruning {
+                continue;
+            }
+
+            if score >= beta {
+                self.ordering.cutoff(board, m, depth);
+                break;
+            }
+
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        if !deadline.stopped() {
+            if let Some(table) = self.table.as_mut() {
+                let bound = transposition::bound(best_score, original_alpha, beta);
+                table.store(key, depth, bound, score_to_table(best_score, ply), Some(best_move));
+            }
+        }
+
+        best_score
+    }
+
+    // Plays out the captures at the end of the search, so that the position
+    // is only evaluated once it is quiet rather than halfway through an
+    // exchange. Captures are compulsory, so the side to move can't stand pat
+    // and take the evaluation instead, and as every capture takes pieces off
+    // the board the exchanges always come to an end.
+    fn quiescence(&mut self, board: &mut checkers::Board, mut alpha: Score, beta: Score, deadline: &mut bot::Deadline) -> Score {
+        if deadline.poll() {
+            return 0;
+        }
+
+        let ply = self.ply(board);
+        if ply >= MAX_PLY {
+            return evaluate(board);
+        }
+
+        let mut moves = checkers::MoveList::new();
+        board.generate_moves(&mut moves);
+
+        // the side to move has lost
+        if moves.is_empty() {
+            return -(WIN - ply as Score);
+        }
+
+        if moves[0].captures.is_empty() {
+            return evaluate(board);
+        }
+
+        self.ordering.order(board, &mut moves, None);
+
+        let mut best_score = -INFINITY;
+
+        for m in moves.iter() {
+            board.push_move(m);
+            let score = -self.quiescence(board, -beta, -alpha, deadline);
+            board.pop_move().expect("the line searched fits in the history");
+
+            if score > best_score {
+                best_score = score;
+            }
+
+            if score >= beta {
+                break;
+            }
+
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        best_score
+    }
+}
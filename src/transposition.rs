@@ -0,0 +1,142 @@
+use crate::checkers;
+use crate::search;
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+// the largest table the hash option takes, the whole table is allocated
+// up front
+pub const MAX_SIZE_MB: usize = 1024;
+
+// What a stored score says about the position's value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bound {
+    // the value to the depth searched
+    Exact,
+    // the search failed high, the value is at least the score
+    Lower,
+    // the search failed low, the value is at most the score
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    key: u64,
+    // the search the entry was stored by
+    age: u32,
+    // plies searched below the position
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: search::Score,
+    // the move that scored best or caused the cutoff, None for a position
+    // without moves or one only evaluated
+    pub best_move: Option<checkers::Move>,
+}
+
+impl Bound {
+    // Whether a stored score with this bound settles the position for a
+    // search with the window alpha to beta, saving searching it again.
+    pub fn settles(self, score: search::Score, alpha: search::Score, beta: search::Score) -> bool {
+        match self {
+            Bound::Exact => true,
+            Bound::Lower => score >= beta,
+            Bound::Upper => score <= alpha,
+        }
+    }
+}
+
+// What a search of the window alpha to beta that returned `score` found out
+// about the value.
+pub fn bound(score: search::Score, alpha: search::Score, beta: search::Score) -> Bound {
+    if score <= alpha {
+        Bound::Upper
+    } else if score >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    }
+}
+
+// Search results by position, kept in a fixed number of slots picked by the
+// hash key. A slot keeps the deeper of two results for different positions,
+// as it saved the most work, unless it is left over from an earlier search.
+#[derive(Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    size_mb: usize,
+    // counts the searches the table has been used for
+    age: u32,
+    // the rules the entries were searched under, the same position can be
+    // worth something else under other rules
+    rules: Option<checkers::Rules>,
+}
+
+impl TranspositionTable {
+    // A table taking up to size_mb megabytes, rounded down to a power of two
+    // number of slots.
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let slots = (size_mb * 1024 * 1024 / std::mem::size_of::<Option<Entry>>()).max(1);
+        let slots = 1 << slots.ilog2();
+
+        TranspositionTable {
+            entries: vec![None; slots],
+            size_mb,
+            age: 0,
+            rules: None,
+        }
+    }
+
+    // the size asked for, the table itself may be a little smaller
+    pub fn size_mb(&self) -> usize {
+        self.size_mb
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.rules = None;
+    }
+
+    // Empties the table when it was filled under other rules.
+    pub fn set_rules(&mut self, rules: checkers::Rules) {
+        if self.rules != Some(rules) {
+            self.clear();
+            self.rules = Some(rules);
+        }
+    }
+
+    // Marks what is stored from now on as belonging to a new search, so that
+    // older entries give way to it.
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<&Entry> {
+        self.entries[self.slot(key)].as_ref().filter(|entry| entry.key == key)
+    }
+
+    pub fn store(&mut self, key: u64, depth: u32, bound: Bound, score: search::Score, best_move: Option<checkers::Move>) {
+        let slot = self.slot(key);
+        let replace = match &self.entries[slot] {
+            Some(entry) => entry.key == key || entry.age != self.age || entry.depth <= depth,
+            None => true,
+        };
+
+        if replace {
+            self.entries[slot] = Some(Entry { key, age: self.age, depth, bound, score, best_move });
+        }
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> TranspositionTable {
+        TranspositionTable::new(DEFAULT_SIZE_MB)
+    }
+}
+
+impl std::fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TranspositionTable").field("slots", &self.entries.len()).field("size_mb", &self.size_mb).field("rules", &self.rules).finish()
+    }
+}